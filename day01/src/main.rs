@@ -1,37 +1,353 @@
-use std::fs;
+use std::{fs::File, io::{self, BufRead, BufReader, Lines}, collections::{BinaryHeap, BTreeMap}, cmp::Reverse, env, fmt::Display};
 
-fn main() {
-    let string = fs::read_to_string("input.txt").unwrap();
-    
-    let calories = string.lines();
-
-
-    let mut current_elf_sum: usize = 0;
-
-    // Top three, best is first
-    let mut top_three: [usize; 3] = [0,0,0];
-
-    for calorie in calories {
-        if let "" = calorie {
-            // Update top three
-            for place in 0..3 {
-                if current_elf_sum > top_three[place] {
-                    for later_place in (place + 1)..3 {
-                        top_three[later_place] = top_three[later_place-1];
-                    }
-                    top_three[place] = current_elf_sum;
-                    break;
+/// The calorie group carried by a single elf
+#[derive(Debug, PartialEq)]
+struct Elf {
+    total: usize,
+    items: usize
+}
+
+/// Why an inventory could not be read, lines are numbered from 1
+#[derive(Debug)]
+enum InventoryError {
+    /// Reading the line failed
+    Read { line: usize, error: io::Error },
+    /// The line is not a number of calories
+    InvalidCalories { line: usize, text: String }
+}
+
+impl Display for InventoryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InventoryError::Read { line, error } => write!(f, "line {line}: {error}"),
+            InventoryError::InvalidCalories { line, text } => write!(f, "line {line}: {text:?} is not a number of calories"),
+        }
+    }
+}
+
+/// Reads calorie groups line by line, so the input never has to be in memory at once.
+/// Iteration ends after the first error.
+struct Elves<R: BufRead> {
+    lines: Lines<R>,
+    /// Number of lines read so far
+    line: usize,
+    failed: bool
+}
+
+impl<R: BufRead> Elves<R> {
+    fn new(reader: R) -> Elves<R> {
+        Elves { lines: reader.lines(), line: 0, failed: false }
+    }
+}
+
+impl<R: BufRead> Iterator for Elves<R> {
+    type Item = Result<Elf, InventoryError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+
+        let mut elf = Elf { total: 0, items: 0 };
+
+        for line in self.lines.by_ref() {
+            self.line += 1;
+
+            let line = match line {
+                Ok(line) => line,
+                Err(error) => {
+                    self.failed = true;
+                    return Some(Err(InventoryError::Read { line: self.line, error }));
                 }
+            };
+            let calorie = line.trim();
+
+            if calorie.is_empty() {
+                // Skip runs of blank lines between elves
+                if elf.items == 0 {
+                    continue;
+                }
+                return Some(Ok(elf));
             }
-            
-            print!("Top three: {:?} \r", top_three);
 
-            // Reset counter for next elf
-            current_elf_sum = 0;
-            continue;
+            match calorie.parse::<usize>() {
+                Ok(calorie) => elf.total += calorie,
+                Err(_) => {
+                    self.failed = true;
+                    return Some(Err(InventoryError::InvalidCalories { line: self.line, text: calorie.to_string() }));
+                }
+            }
+            elf.items += 1;
+        }
+
+        // The last elf is not necessarily followed by a blank line
+        if elf.items > 0 {
+            Some(Ok(elf))
+        } else {
+            None
+        }
+    }
+}
+
+/// Ranks the elves by total calories and returns the best `n` as `(elf_index, total)` pairs,
+/// best first. Elf indices are zero-based positions in the input, ties go to the lower index.
+fn top_elves(elves: impl Iterator<Item = Elf>, n: usize) -> Vec<(usize, usize)> {
+    // Min-heap holding the current top n, so the worst of them is evicted first
+    // N may be far larger than the input, so it only bounds the capacity hint
+    let mut heap = BinaryHeap::with_capacity(n.saturating_add(1).min(1024));
+
+    for (index, elf) in elves.enumerate() {
+        heap.push(Reverse((elf.total, Reverse(index))));
+
+        if heap.len() > n {
+            heap.pop();
+        }
+    }
+
+    let mut ranking: Vec<_> = heap.into_iter().map(|Reverse((total, Reverse(index)))| (index, total)).collect();
+    ranking.sort_by_key(|&(index, total)| (Reverse(total), index));
+    ranking
+}
+
+/// Summary of all calorie groups in an inventory
+#[derive(Debug, PartialEq)]
+struct Statistics {
+    /// Sorted totals of all elves
+    totals: Vec<usize>,
+    /// Item count per elf, in input order
+    item_counts: Vec<usize>
+}
+
+impl Statistics {
+    const HISTOGRAM_BUCKETS: usize = 10;
+    const HISTOGRAM_WIDTH: usize = 50;
+
+    /// Returns `None` when there are no elves to summarize
+    fn build(elves: impl Iterator<Item = Elf>) -> Option<Statistics> {
+        let (mut totals, item_counts): (Vec<_>, Vec<_>) = elves.map(|elf| (elf.total, elf.items)).unzip();
+
+        if totals.is_empty() {
+            return None;
+        }
+
+        totals.sort_unstable();
+
+        Some(Statistics { totals, item_counts })
+    }
+
+    fn elves(&self) -> usize {
+        self.totals.len()
+    }
+
+    fn mean(&self) -> f64 {
+        self.totals.iter().sum::<usize>() as f64 / self.elves() as f64
+    }
+
+    fn median(&self) -> f64 {
+        let middle = self.elves() / 2;
+
+        if self.elves().is_multiple_of(2) {
+            (self.totals[middle - 1] + self.totals[middle]) as f64 / 2.0
+        } else {
+            self.totals[middle] as f64
+        }
+    }
+
+    /// Nearest-rank percentile, `percent` is capped at 100
+    fn percentile(&self, percent: usize) -> usize {
+        let rank = (percent.min(100) * self.elves()).div_ceil(100);
+
+        self.totals[rank.saturating_sub(1)]
+    }
+
+    /// Number of elves per equally wide bucket of totals, as `(bucket_start, elves)`
+    fn histogram(&self) -> Vec<(usize, usize)> {
+        let min = self.totals[0];
+        let max = self.totals[self.elves() - 1];
+        let width = ((max - min) / Self::HISTOGRAM_BUCKETS + 1).max(1);
+
+        let mut buckets: Vec<_> = (0..Self::HISTOGRAM_BUCKETS).map(|bucket| (min + bucket * width, 0)).collect();
+
+        for total in &self.totals {
+            buckets[(total - min) / width].1 += 1;
+        }
+
+        buckets
+    }
+}
+
+impl Display for Statistics {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Elves: {}", self.elves())?;
+        writeln!(f, "Items: {}", self.item_counts.iter().sum::<usize>())?;
+
+        // How many elves carry each number of items
+        let mut item_counts = BTreeMap::new();
+        for items in &self.item_counts {
+            *item_counts.entry(items).or_insert(0) += 1;
+        }
+        writeln!(f, "Items per elf:")?;
+        for (items, elves) in item_counts {
+            writeln!(f, "  {items:>3} items: {elves} elves")?;
+        }
+
+        writeln!(f, "Totals:")?;
+        writeln!(f, "  min:    {}", self.totals[0])?;
+        writeln!(f, "  mean:   {:.1}", self.mean())?;
+        writeln!(f, "  median: {:.1}", self.median())?;
+        for percent in [25, 75, 90, 99] {
+            writeln!(f, "  p{percent}:    {}", self.percentile(percent))?;
+        }
+        writeln!(f, "  max:    {}", self.totals[self.elves() - 1])?;
+
+        writeln!(f, "Histogram:")?;
+        let histogram = self.histogram();
+        let most = histogram.iter().map(|(_, elves)| *elves).max().unwrap_or(1);
+        for (start, elves) in histogram {
+            let bar = "#".repeat((elves * Self::HISTOGRAM_WIDTH).div_ceil(most));
+            writeln!(f, "  {start:>7} | {bar} {elves}")?;
+        }
+
+        Ok(())
+    }
+}
+
+fn main() {
+    let file = File::open("input.txt").unwrap();
+
+    // Stops at the first broken line, which is reported instead of any results
+    let mut error = None;
+    let elves = Elves::new(BufReader::new(file)).map_while(|elf| elf.map_err(|elf_error| error = Some(elf_error)).ok());
+
+    // Pass `stats` to print a report of the whole inventory instead
+    if env::args().nth(1).as_deref() == Some("stats") {
+        let statistics = Statistics::build(elves);
+        if let Some(error) = error {
+            println!("Invalid inventory: {error}");
+            return;
+        }
+
+        match statistics {
+            Some(statistics) => print!("{statistics}"),
+            None => println!("No elves in input"),
         }
-        
-        current_elf_sum += calorie.parse::<usize>().unwrap();
+        return;
+    }
+
+    let top_three = top_elves(elves, 3);
+    if let Some(error) = error {
+        println!("Invalid inventory: {error}");
+        return;
+    }
+
+    if let Some((index, total)) = top_three.first() {
+        println!("Elf {index} carries the most: {total}");
+    }
+    println!("Top three: {:?}", top_three);
+    println!("{}", top_three.iter().map(|(_, total)| total).sum::<usize>());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn elves(input: &str) -> impl Iterator<Item = Elf> + '_ {
+        Elves::new(input.as_bytes()).map(Result::unwrap)
+    }
+
+    #[test]
+    fn test_last_elf_without_blank_line() {
+        let input = "1000\n2000\n\n4000\n\n5000\n6000";
+
+        let elves: Vec<_> = elves(input).collect();
+
+        assert_eq!(elves, vec![
+            Elf { total: 3000, items: 2 },
+            Elf { total: 4000, items: 1 },
+            Elf { total: 11000, items: 2 },
+        ]);
+    }
+
+    #[test]
+    fn test_trailing_and_repeated_blank_lines() {
+        let input = "\n1000\n\n\n2000\n\n";
+
+        let totals: Vec<_> = elves(input).map(|elf| elf.total).collect();
+
+        assert_eq!(totals, vec![1000, 2000]);
+    }
+
+    #[test]
+    fn test_top_elves() {
+        let input = "1000\n2000\n3000\n\n4000\n\n5000\n6000\n\n7000\n8000\n9000\n\n10000";
+
+        let ranking = top_elves(elves(input), 3);
+
+        assert_eq!(ranking, vec![(3, 24000), (2, 11000), (4, 10000)]);
+        assert_eq!(ranking.iter().map(|(_, total)| total).sum::<usize>(), 45000);
+    }
+
+    #[test]
+    fn test_top_elves_ties_and_short_input() {
+        let input = "5\n\n7\n\n5\n\n7";
+
+        assert_eq!(top_elves(elves(input), 3), vec![(1, 7), (3, 7), (0, 5)]);
+        assert_eq!(top_elves(elves(input), 10).len(), 4);
+        assert!(top_elves(elves(input), 0).is_empty());
+        assert_eq!(top_elves(elves(input), usize::MAX).len(), 4);
+    }
+
+    #[test]
+    fn test_statistics() {
+        let input = "1000\n2000\n3000\n\n4000\n\n5000\n6000\n\n7000\n8000\n9000\n\n10000";
+
+        let statistics = Statistics::build(elves(input)).unwrap();
+
+        assert_eq!(statistics.elves(), 5);
+        assert_eq!(statistics.item_counts, vec![3, 1, 2, 3, 1]);
+        assert_eq!(statistics.mean(), 11000.0);
+        assert_eq!(statistics.median(), 10000.0);
+        assert_eq!(statistics.percentile(0), 4000);
+        assert_eq!(statistics.percentile(50), 10000);
+        assert_eq!(statistics.percentile(100), 24000);
+
+        let histogram = statistics.histogram();
+        assert_eq!(histogram.len(), Statistics::HISTOGRAM_BUCKETS);
+        assert_eq!(histogram.iter().map(|(_, elves)| elves).sum::<usize>(), 5);
+        assert_eq!(histogram[0], (4000, 2));
+        assert_eq!(histogram[9].1, 1);
+    }
+
+    #[test]
+    fn test_statistics_edge_cases() {
+        assert_eq!(Statistics::build(elves("")), None);
+
+        let statistics = Statistics::build(elves("7\n\n7")).unwrap();
+        assert_eq!(statistics.median(), 7.0);
+        assert_eq!(statistics.histogram()[0], (7, 2));
+    }
+
+    /// A stream failing on every read, chained after some lines in the tests
+    struct BrokenStream;
+
+    impl io::Read for BrokenStream {
+        fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+            Err(io::Error::other("stream broke"))
+        }
+    }
+
+    #[test]
+    fn test_inventory_errors() {
+        let mut elves = Elves::new("1000\n\n2000\n20OO\n3000".as_bytes());
+
+        assert_eq!(elves.next().unwrap().unwrap(), Elf { total: 1000, items: 1 });
+        let error = elves.next().unwrap().unwrap_err();
+        assert!(matches!(&error, InventoryError::InvalidCalories { line: 4, text } if text == "20OO"));
+        assert_eq!(error.to_string(), "line 4: \"20OO\" is not a number of calories");
+        assert!(elves.next().is_none());
+
+        let mut elves = Elves::new(BufReader::new(io::Read::chain("1000\n2000\n".as_bytes(), BrokenStream)));
+        assert!(matches!(elves.next(), Some(Err(InventoryError::Read { line: 3, .. }))));
+        assert!(elves.next().is_none());
     }
-    println!("\n{}", top_three.iter().sum::<usize>());
 }
@@ -1,114 +1,343 @@
-use std::{fs, cmp::Ordering};
+use std::{fs, cmp::Ordering, env, fmt::{Debug, Display}, ptr};
 
-#[derive(PartialEq)]
-enum Shape {
-    Rock,
-    Paper,
-    Scissors
+/// A shape of a cyclic hand game and the guide symbols it is written as
+struct ShapeDef {
+    name: &'static str,
+    opponent_symbol: &'static str,
+    own_symbol: &'static str
 }
-impl Shape {
-    fn get_score(&self) -> usize {
-        match self {
-            Shape::Rock => 1,
-            Shape::Paper => 2,
-            Shape::Scissors => 3,
+
+/// Rules of a hand game with any number of shapes
+struct Game {
+    shapes: Vec<ShapeDef>,
+    /// `table[a][b]` is the outcome of shape `a` played against shape `b`
+    table: Vec<Vec<Ordering>>
+}
+
+impl Game {
+    /// Builds the balanced game on an odd number of shapes, in which every shape
+    /// beats the shapes an odd number of places before it in the cycle
+    fn cyclic(shapes: Vec<ShapeDef>) -> Game {
+        let count = shapes.len();
+
+        if count.is_multiple_of(2) {
+            panic!("Cyclic game needs an odd number of shapes, got {}", count);
         }
-    }
 
-    fn opponent_should_be(&self, outcome: std::cmp::Ordering) -> &Shape {
-        match outcome {
-            Ordering::Less => {
-                match self {
-                    Shape::Rock => &Shape::Scissors,
-                    Shape::Paper => &Shape::Rock,
-                    Shape::Scissors => &Shape::Paper,
+        let table = (0..count).map(|a| {
+            (0..count).map(|b| {
+                match (a + count - b) % count {
+                    0 => Ordering::Equal,
+                    distance if distance % 2 == 1 => Ordering::Greater,
+                    _ => Ordering::Less,
                 }
-            },
-            Ordering::Equal => {
-                match self {
-                    Shape::Rock => &Shape::Rock,
-                    Shape::Paper => &Shape::Paper,
-                    Shape::Scissors => &Shape::Scissors,
-                }
-            }
-            Ordering::Greater => {
-                match self {
-                    Shape::Rock => &Shape::Paper,
-                    Shape::Paper => &Shape::Scissors,
-                    Shape::Scissors => &Shape::Rock,
-                }
-            }
-        }
+            }).collect()
+        }).collect();
+
+        Game { shapes, table }
+    }
+
+    fn rock_paper_scissors() -> Game {
+        Game::cyclic(vec![
+            ShapeDef { name: "Rock", opponent_symbol: "A", own_symbol: "X" },
+            ShapeDef { name: "Paper", opponent_symbol: "B", own_symbol: "Y" },
+            ShapeDef { name: "Scissors", opponent_symbol: "C", own_symbol: "Z" },
+        ])
+    }
+
+    fn rock_paper_scissors_lizard_spock() -> Game {
+        Game::cyclic(vec![
+            ShapeDef { name: "Rock", opponent_symbol: "A", own_symbol: "X" },
+            ShapeDef { name: "Paper", opponent_symbol: "B", own_symbol: "Y" },
+            ShapeDef { name: "Scissors", opponent_symbol: "C", own_symbol: "Z" },
+            ShapeDef { name: "Spock", opponent_symbol: "D", own_symbol: "W" },
+            ShapeDef { name: "Lizard", opponent_symbol: "E", own_symbol: "V" },
+        ])
     }
+
+    fn shape(&self, index: usize) -> Shape<'_> {
+        Shape { game: self, index }
+    }
+
+    fn shapes(&self) -> impl Iterator<Item = Shape<'_>> {
+        (0..self.shapes.len()).map(|index| self.shape(index))
+    }
+
 }
 
-impl From<&str> for Shape {
-    fn from(char: &str) -> Self {
-        match char {
-            "A" | "X" => Shape::Rock,
-            "B" | "Y" => Shape::Paper,
-            "C" | "Z" => Shape::Scissors,
-            _ => panic!("Unrecognized shape: {}", char)
-        } 
+#[derive(Clone, Copy)]
+struct Shape<'a> {
+    game: &'a Game,
+    index: usize
+}
+
+impl<'a> Shape<'a> {
+    fn get_score(&self) -> usize {
+        self.index + 1
     }
 
+    /// The shape to play against `self` to reach `outcome`. When several shapes
+    /// reach it, the one with the highest score is picked.
+    fn opponent_should_be(&self, outcome: std::cmp::Ordering) -> Shape<'a> {
+        self.game.shapes()
+            .filter(|shape| shape.partial_cmp(self) == Some(outcome))
+            .max_by_key(|shape| shape.get_score())
+            .unwrap()
+    }
 
+    /// Score for playing `self` against `opponent`: the shape score plus the outcome score
+    fn score_against(&self, opponent: &Shape) -> usize {
+        let outcome_score = match self.partial_cmp(opponent).unwrap() {
+            Ordering::Less => 0,
+            Ordering::Equal => 3,
+            Ordering::Greater => 6,
+        };
+
+        self.get_score() + outcome_score
+    }
+
+    /// The response scoring the most against `self`, preferring the higher shape score on ties
+    fn best_response(&self) -> Shape<'a> {
+        self.game.shapes()
+            .max_by_key(|shape| (shape.score_against(self), shape.get_score()))
+            .unwrap()
+    }
+
+    fn name(&self) -> &'static str {
+        self.game.shapes[self.index].name
+    }
 }
 
-impl PartialOrd for Shape {
+impl Debug for Shape<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl PartialEq for Shape<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        ptr::eq(self.game, other.game) && self.index == other.index
+    }
+}
+
+impl PartialOrd for Shape<'_> {
+    /// Shapes of different games are not comparable
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        if !ptr::eq(self.game, other.game) {
+            return None;
+        }
+
+        Some(self.game.table[self.index][other.index])
+    }
+}
+
+/// A guide token that is not a symbol of the game
+#[derive(Debug, PartialEq)]
+struct UnknownSymbol(String);
+
+impl<'a> TryFrom<(&'a Game, &str)> for Shape<'a> {
+    type Error = UnknownSymbol;
+
+    fn try_from((game, symbol): (&'a Game, &str)) -> Result<Self, Self::Error> {
+        match game.shapes.iter().position(|def| def.opponent_symbol == symbol) {
+            Some(index) => Ok(game.shape(index)),
+            None => Err(UnknownSymbol(symbol.to_string()))
+        }
+    }
+}
+
+/// The second column of the strategy guide as an index into the game's own symbols,
+/// whose meaning depends on the interpretation
+#[derive(Debug, PartialEq, Clone, Copy)]
+struct Column(usize);
+
+impl TryFrom<(&Game, &str)> for Column {
+    type Error = UnknownSymbol;
+
+    fn try_from((game, symbol): (&Game, &str)) -> Result<Self, Self::Error> {
+        match game.shapes.iter().position(|def| def.own_symbol == symbol) {
+            Some(index) => Ok(Column(index)),
+            None => Err(UnknownSymbol(symbol.to_string()))
+        }
+    }
+}
+
+/// How the second column of the guide is read
+#[derive(Debug, Clone, Copy)]
+enum Interpretation {
+    /// The column is the shape to play
+    Shape,
+    /// The first three columns (X, Y and Z) are the outcome to reach: lose, draw or win
+    Outcome
+}
+
+impl Interpretation {
+    /// Returns `None` when the column has no meaning under this interpretation
+    fn own_shape<'a>(&self, opponent: Shape<'a>, column: Column) -> Option<Shape<'a>> {
         match self {
-            Shape::Rock => {
-                match other {
-                    Shape::Rock => Some(std::cmp::Ordering::Equal),
-                    Shape::Paper => Some(std::cmp::Ordering::Less),
-                    Shape::Scissors => Some(std::cmp::Ordering::Greater),
-                }
-            },
-            Shape::Paper => {
-                match other {
-                    Shape::Rock => Some(std::cmp::Ordering::Greater),
-                    Shape::Paper => Some(std::cmp::Ordering::Equal),
-                    Shape::Scissors => Some(std::cmp::Ordering::Less),
-                }
-            },
-            Shape::Scissors => {
-                match other {
-                    Shape::Rock => Some(std::cmp::Ordering::Less),
-                    Shape::Paper => Some(std::cmp::Ordering::Greater),
-                    Shape::Scissors => Some(std::cmp::Ordering::Equal),
-                }
+            Interpretation::Shape => Some(opponent.game.shape(column.0)),
+            Interpretation::Outcome => match column.0 {
+                0 => Some(opponent.opponent_should_be(Ordering::Less)),
+                1 => Some(opponent.opponent_should_be(Ordering::Equal)),
+                2 => Some(opponent.opponent_should_be(Ordering::Greater)),
+                _ => None
             }
         }
     }
 }
 
+/// A problem with a line of the strategy guide, lines are numbered from 1
+#[derive(Debug, PartialEq)]
+enum GuideError {
+    /// The line is not an opponent and a column separated by a space
+    Malformed { line: usize, text: String },
+    UnknownShape { line: usize, token: String },
+    UnknownColumn { line: usize, token: String },
+    /// The column is a shape of the game but not one of the three outcomes
+    NotAnOutcome { line: usize, token: String }
+}
+
+impl Display for GuideError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GuideError::Malformed { line, text } => write!(f, "line {line}: malformed round {text:?}"),
+            GuideError::UnknownShape { line, token } => write!(f, "line {line}: unrecognized shape {token:?}"),
+            GuideError::UnknownColumn { line, token } => write!(f, "line {line}: unrecognized column {token:?}"),
+            GuideError::NotAnOutcome { line, token } => write!(f, "line {line}: column {token:?} is not an outcome"),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct Round<'a> {
+    line: usize,
+    opponent: Shape<'a>,
+    column: Column
+}
+
+impl<'a> TryFrom<(&'a Game, usize, &str)> for Round<'a> {
+    type Error = GuideError;
+
+    fn try_from((game, line, text): (&'a Game, usize, &str)) -> Result<Self, Self::Error> {
+        let Some((opponent, column)) = text.trim().split_once(' ') else {
+            return Err(GuideError::Malformed { line, text: text.to_string() });
+        };
+
+        let opponent = Shape::try_from((game, opponent))
+            .map_err(|UnknownSymbol(token)| GuideError::UnknownShape { line, token })?;
+        let column = Column::try_from((game, column.trim()))
+            .map_err(|UnknownSymbol(token)| GuideError::UnknownColumn { line, token })?;
+
+        Ok(Round { line, opponent, column })
+    }
+}
+
+impl Round<'_> {
+    fn score(&self, interpretation: Interpretation) -> Result<usize, GuideError> {
+        match interpretation.own_shape(self.opponent, self.column) {
+            Some(own) => Ok(own.score_against(&self.opponent)),
+            None => Err(GuideError::NotAnOutcome {
+                line: self.line,
+                token: self.opponent.game.shapes[self.column.0].own_symbol.to_string()
+            })
+        }
+    }
+}
+
+/// Parses every non-empty line, reporting all bad lines instead of stopping at the first
+fn parse_guide<'a>(game: &'a Game, input: &str) -> Result<Vec<Round<'a>>, Vec<GuideError>> {
+    let (rounds, errors): (Vec<_>, Vec<_>) = input.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| Round::try_from((game, index + 1, line)))
+        .partition(|round| round.is_ok());
+
+    if errors.is_empty() {
+        Ok(rounds.into_iter().map(Result::unwrap).collect())
+    } else {
+        Err(errors.into_iter().map(|error| error.unwrap_err()).collect())
+    }
+}
+
+/// Fails with every round that cannot be scored under the interpretation
+fn total_score(guide: &[Round], interpretation: Interpretation) -> Result<usize, Vec<GuideError>> {
+    let (scores, errors): (Vec<_>, Vec<_>) = guide.iter()
+        .map(|round| round.score(interpretation))
+        .partition(|score| score.is_ok());
+
+    if errors.is_empty() {
+        Ok(scores.into_iter().map(Result::unwrap).sum())
+    } else {
+        Err(errors.into_iter().map(|error| error.unwrap_err()).collect())
+    }
+}
+
+/// Comparison of a guide against the guide answering every opponent shape optimally
+struct Optimization<'a> {
+    /// Best response per opponent shape, for the shapes occurring in the guide
+    responses: Vec<(Shape<'a>, Shape<'a>)>,
+    given_score: usize,
+    optimal_score: usize
+}
+
+fn optimize<'a>(game: &'a Game, guide: &[Round<'a>], interpretation: Interpretation) -> Result<Optimization<'a>, Vec<GuideError>> {
+    let responses = game.shapes()
+        .filter(|shape| guide.iter().any(|round| round.opponent == *shape))
+        .map(|shape| (shape, shape.best_response()))
+        .collect();
+
+    let optimal_score = guide.iter().map(|round| round.opponent.best_response().score_against(&round.opponent)).sum();
+
+    Ok(Optimization { responses, given_score: total_score(guide, interpretation)?, optimal_score })
+}
+
 fn main() {
     let string: String = fs::read_to_string("input.txt").unwrap();
+    let args: Vec<String> = env::args().skip(1).collect();
 
-    let mut total_score: usize = 0;
-    for line in string.lines() {
-        if let [opponent, outcome] = line.split(' ').collect::<Vec<_>>().as_slice() {
-            let outcome_ord = match outcome {
-                &"X" => Ordering::Less,
-                &"Y" => {
-                    total_score += 3;
-                    Ordering::Equal
-                }
-                &"Z" => {
-                    total_score += 6;
-                    Ordering::Greater
-                }
-                _ => unreachable!()
-            };
-            
-            let opponent: Shape = (*opponent).into();
+    // Pass `rpsls` to play Rock-Paper-Scissors-Lizard-Spock
+    let game = if args.iter().any(|arg| arg == "rpsls") {
+        Game::rock_paper_scissors_lizard_spock()
+    } else {
+        Game::rock_paper_scissors()
+    };
 
-            total_score += opponent.opponent_should_be(outcome_ord).get_score();
+    let guide = match parse_guide(&game, &string) {
+        Ok(guide) => guide,
+        Err(errors) => {
+            println!("Strategy guide has {} bad lines:", errors.len());
+            for error in errors {
+                println!("  {error}");
+            }
+            return;
+        }
+    };
+
+    for interpretation in [Interpretation::Shape, Interpretation::Outcome] {
+        match total_score(&guide, interpretation) {
+            Ok(score) => println!("Total score ({:?}): {}", interpretation, score),
+            Err(errors) => {
+                println!("Total score ({:?}): {} rounds cannot be scored, first at {}", interpretation, errors.len(), errors[0]);
+            }
         }
     }
 
-    println!("Total score: {}", total_score);
+    // Pass `optimize` to compare the guide against the best possible one
+    if args.iter().any(|arg| arg == "optimize") {
+        for interpretation in [Interpretation::Shape, Interpretation::Outcome] {
+            let Ok(optimization) = optimize(&game, &guide, interpretation) else {
+                continue;
+            };
+
+            if let Interpretation::Shape = interpretation {
+                println!("Optimal guide: {}", optimization.optimal_score);
+                for (opponent, response) in optimization.responses {
+                    println!("  {:?} -> {:?}", opponent, response);
+                }
+            }
+            println!("{} better than the guide ({:?})", optimization.optimal_score - optimization.given_score, interpretation);
+        }
+    }
 }
 
 #[cfg(test)]
@@ -117,12 +346,87 @@ mod tests {
 
     #[test]
     fn test_ordering() {
-        assert!(Shape::Paper > Shape::Rock);
-        assert!(Shape::Paper == Shape::Paper);
-        assert!(Shape::Rock > Shape::Scissors);
-        assert!(Shape::Scissors > Shape::Paper);
-        
-        assert_eq!(Shape::Scissors.partial_cmp(&Shape::Paper), Some(std::cmp::Ordering::Greater));
-        assert_eq!(Shape::Scissors.partial_cmp(&Shape::Scissors), Some(std::cmp::Ordering::Equal));
+        let game = Game::rock_paper_scissors();
+        let [rock, paper, scissors] = [0, 1, 2].map(|index| game.shape(index));
+
+        assert!(paper > rock);
+        assert!(paper == paper);
+        assert!(rock > scissors);
+        assert!(scissors > paper);
+
+        assert_eq!(scissors.partial_cmp(&paper), Some(std::cmp::Ordering::Greater));
+        assert_eq!(scissors.partial_cmp(&scissors), Some(std::cmp::Ordering::Equal));
+    }
+
+    #[test]
+    fn test_rock_paper_scissors_lizard_spock() {
+        let game = Game::rock_paper_scissors_lizard_spock();
+        let [rock, paper, scissors, spock, lizard] = [0, 1, 2, 3, 4].map(|index| game.shape(index));
+
+        assert!(rock > scissors && rock > lizard);
+        assert!(paper > rock && paper > spock);
+        assert!(scissors > paper && scissors > lizard);
+        assert!(spock > scissors && spock > rock);
+        assert!(lizard > spock && lizard > paper);
+
+        // Every shape beats exactly half of the others
+        for shape in game.shapes() {
+            assert_eq!(game.shapes().filter(|other| shape > *other).count(), 2);
+        }
+
+        assert_eq!(rock.opponent_should_be(Ordering::Greater), spock);
+        assert_eq!(rock.opponent_should_be(Ordering::Less), lizard);
+        assert_eq!(rock.partial_cmp(&Game::rock_paper_scissors().shape(0)), None);
+    }
+
+    #[test]
+    fn test_interpretations() {
+        let game = Game::rock_paper_scissors();
+        let guide = parse_guide(&game, "A Y\nB X\nC Z\n").unwrap();
+
+        assert_eq!(guide[0], Round { line: 1, opponent: game.shape(0), column: Column(1) });
+        assert_eq!(total_score(&guide, Interpretation::Shape), Ok(15));
+        assert_eq!(total_score(&guide, Interpretation::Outcome), Ok(12));
+
+        let game = Game::rock_paper_scissors_lizard_spock();
+        let guide = parse_guide(&game, "A W\nE V\n").unwrap();
+
+        assert_eq!(total_score(&guide, Interpretation::Shape), Ok((4 + 6) + (5 + 3)));
+    }
+
+    #[test]
+    fn test_optimize() {
+        let game = Game::rock_paper_scissors();
+        let [rock, paper, scissors] = [0, 1, 2].map(|index| game.shape(index));
+
+        assert_eq!(rock.best_response(), paper);
+        assert_eq!(scissors.best_response(), rock);
+
+        let guide = parse_guide(&game, "A Y\nB X\nC Z\n").unwrap();
+        let optimization = optimize(&game, &guide, Interpretation::Outcome).unwrap();
+
+        assert_eq!(optimization.responses, vec![(rock, paper), (paper, scissors), (scissors, rock)]);
+        assert_eq!(optimization.given_score, 12);
+        assert_eq!(optimization.optimal_score, 8 + 9 + 7);
+    }
+
+    #[test]
+    fn test_guide_errors() {
+        let game = Game::rock_paper_scissors();
+
+        assert_eq!(Shape::try_from((&game, "D")), Err(UnknownSymbol("D".to_string())));
+        assert_eq!(Column::try_from((&game, "Z")), Ok(Column(2)));
+
+        let errors = parse_guide(&game, "A Y\nAY\n\nD X\nB Q\nC Z").unwrap_err();
+        assert_eq!(errors, vec![
+            GuideError::Malformed { line: 2, text: "AY".to_string() },
+            GuideError::UnknownShape { line: 4, token: "D".to_string() },
+            GuideError::UnknownColumn { line: 5, token: "Q".to_string() },
+        ]);
+        assert_eq!(errors[2].to_string(), "line 5: unrecognized column \"Q\"");
+
+        let game = Game::rock_paper_scissors_lizard_spock();
+        let guide = parse_guide(&game, "A X\nB W").unwrap();
+        assert_eq!(total_score(&guide, Interpretation::Outcome), Err(vec![GuideError::NotAnOutcome { line: 2, token: "W".to_string() }]));
     }
 }
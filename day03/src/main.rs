@@ -1,4 +1,4 @@
-use std::fs;
+use std::{fs, fmt::Display};
 
 
 fn get_char_priority(character: u8) -> u8 {
@@ -8,37 +8,191 @@ fn get_char_priority(character: u8) -> u8 {
         character - b'A' + 27
     }
 }
+
+fn get_priority_char(priority: u8) -> u8 {
+    if priority <= 26 {
+        b'a' + priority - 1
+    } else {
+        b'A' + priority - 27
+    }
+}
+
+/// Set of item types, bit `priority - 1` is set when the item is present
+#[derive(Debug, PartialEq, Clone, Copy)]
+struct Rucksack(u64);
+
+impl Rucksack {
+    fn new(items: &[u8]) -> Rucksack {
+        Rucksack(items.iter().fold(0, |set, item| set | 1 << (get_char_priority(*item) - 1)))
+    }
+
+    fn intersection(&self, other: &Rucksack) -> Rucksack {
+        Rucksack(self.0 & other.0)
+    }
+
+    /// Sum of the priorities of all items in the set
+    fn priority_sum(&self) -> u64 {
+        (0..52).filter(|bit| self.0 & 1 << bit != 0).map(|bit| bit + 1).sum()
+    }
+
+    fn items(&self) -> Vec<u8> {
+        (1..=52).filter(|priority| self.0 & 1 << (priority - 1) != 0).map(get_priority_char).collect()
+    }
+
+    /// Items shared by both compartments, the first and second half of the line
+    fn shared_compartment_items(line: &str) -> Rucksack {
+        let (first, second) = line.as_bytes().split_at(line.len() / 2);
+
+        Rucksack::new(first).intersection(&Rucksack::new(second))
+    }
+}
+
+fn compartment_priority_sum(lines: &[&str]) -> u64 {
+    lines.iter().map(|line| Rucksack::shared_compartment_items(line).priority_sum()).sum()
+}
+
+/// Why a group has no single badge, groups are numbered from 1
+#[derive(Debug, PartialEq)]
+enum BadgeError {
+    /// The input ran out before the last group was full
+    IncompleteGroup { group: usize, size: usize },
+    NoCommonItem { group: usize },
+    MultipleCommonItems { group: usize, items: Vec<u8> }
+}
+
+/// Finds the badge of every group of `group_size` consecutive rucksacks
+fn find_badges(lines: &[&str], group_size: usize) -> Vec<Result<u8, BadgeError>> {
+    assert!(group_size > 0, "Badge groups need at least one rucksack");
+
+    lines.chunks(group_size)
+        .enumerate()
+        .map(|(index, group)| {
+            let group_number = index + 1;
+
+            if group.len() < group_size {
+                return Err(BadgeError::IncompleteGroup { group: group_number, size: group.len() });
+            }
+
+            let common = group.iter()
+                .map(|line| Rucksack::new(line.as_bytes()))
+                .reduce(|common, rucksack| common.intersection(&rucksack))
+                .unwrap();
+
+            let items = common.items();
+            match items[..] {
+                [badge] => Ok(badge),
+                [] => Err(BadgeError::NoCommonItem { group: group_number }),
+                _ => Err(BadgeError::MultipleCommonItems { group: group_number, items })
+            }
+        })
+        .collect()
+}
+
+impl Display for BadgeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BadgeError::IncompleteGroup { group, size } => write!(f, "group {group}: only {size} rucksacks left"),
+            BadgeError::NoCommonItem { group } => write!(f, "group {group}: no item carried by every rucksack"),
+            BadgeError::MultipleCommonItems { group, items } => {
+                write!(f, "group {group}: several items carried by every rucksack: {}", String::from_utf8_lossy(items))
+            }
+        }
+    }
+}
+
+fn badge_priority_sum(badges: &[Result<u8, BadgeError>]) -> u64 {
+    badges.iter().flatten().map(|badge| get_char_priority(*badge) as u64).sum()
+}
+
+/// Something wrong with a single rucksack, lines are numbered from 1
+#[derive(Debug, PartialEq)]
+enum RucksackProblem {
+    /// Not a letter, so it has no priority
+    InvalidItem { line: usize, item: char },
+    /// Cannot be split into two equal compartments
+    OddLength { line: usize, length: usize },
+    NoSharedItem { line: usize }
+}
+
+impl RucksackProblem {
+    /// Whether the priority sums would be meaningless with this problem present
+    fn is_fatal(&self) -> bool {
+        !matches!(self, RucksackProblem::NoSharedItem { .. })
+    }
+}
+
+impl Display for RucksackProblem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RucksackProblem::InvalidItem { line, item } => write!(f, "line {line}: item {item:?} is not a letter"),
+            RucksackProblem::OddLength { line, length } => write!(f, "line {line}: {length} items do not split into two compartments"),
+            RucksackProblem::NoSharedItem { line } => write!(f, "line {line}: no item in both compartments"),
+        }
+    }
+}
+
+/// Lists every problem of every rucksack, the shared item is only checked on otherwise valid rucksacks
+fn validate(lines: &[&str]) -> Vec<RucksackProblem> {
+    let mut problems = Vec::new();
+
+    for (index, line) in lines.iter().enumerate() {
+        let line_number = index + 1;
+        let before = problems.len();
+
+        for item in line.chars().filter(|item| !item.is_ascii_alphabetic()) {
+            problems.push(RucksackProblem::InvalidItem { line: line_number, item });
+        }
+
+        if line.len() % 2 == 1 {
+            problems.push(RucksackProblem::OddLength { line: line_number, length: line.len() });
+        }
+
+        if problems.len() == before && Rucksack::shared_compartment_items(line).0 == 0 {
+            problems.push(RucksackProblem::NoSharedItem { line: line_number });
+        }
+    }
+
+    problems
+}
+
 fn main() {
     let string: String = fs::read_to_string("input.txt").unwrap();
 
-    let mut result: u64 = 0;
     let strings: Vec<_> = string.lines().map(|l| l.trim()).collect();
-    let strings_slice = &strings;
-    let iter = strings_slice.chunks(3);
-
-    for triplet in iter {
-        let first = triplet[0];
-        let second = triplet[1];
-        let third = triplet[2];
-
-        'outer: for first_byte in first.as_bytes() {
-            for second_byte in second.as_bytes() {
-                for third_byte in third.as_bytes() {
-                    if first_byte == second_byte && second_byte == third_byte {
-                        result += get_char_priority(*first_byte) as u64;
-                        break 'outer;
-                    }
-                }
-            }
+
+    let problems = validate(&strings);
+    for problem in &problems {
+        if problem.is_fatal() {
+            println!("Invalid rucksack: {problem}");
+        } else {
+            println!("Warning: {problem}");
         }
     }
+    if problems.iter().any(RucksackProblem::is_fatal) {
+        return;
+    }
+
+    println!("Compartments: {}", compartment_priority_sum(&strings));
 
-    println!("{}", result);
+    let badges = find_badges(&strings, 3);
+    for error in badges.iter().filter_map(|badge| badge.as_ref().err()) {
+        println!("Skipping {error}");
+    }
+    println!("Badges: {}", badge_priority_sum(&badges));
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::get_char_priority;
+    use crate::*;
+
+    const EXAMPLE: [&str; 6] = [
+        "vJrwpWtwJgWrhcsFMMfFFhFp",
+        "jqHRNqRjqzjGDLGLrsFMfFZSrLrFZsSL",
+        "PmmdzqPrVvPwwTWBwg",
+        "wMqvLMZHhHMvwLHjbvcjnnSBnvTQFn",
+        "ttgJtRGJQctTZtZT",
+        "CrZsJsPPZsGzwwsLwLmpwMDw",
+    ];
 
     #[test]
     fn test_split_str() {
@@ -56,4 +210,58 @@ mod tests {
         assert_eq!(get_char_priority(b'A'), 27);
         assert_eq!(get_char_priority(b'Z'), 52);
     }
+
+    #[test]
+    fn test_rucksack() {
+        let shared = Rucksack::shared_compartment_items(EXAMPLE[0]);
+
+        assert_eq!(shared, Rucksack::new(b"p"));
+        assert_eq!(Rucksack::new(b"aZ").priority_sum(), 53);
+        assert_eq!(Rucksack::new(b"abc").intersection(&Rucksack::new(b"bcd")), Rucksack::new(b"cb"));
+    }
+
+    #[test]
+    fn test_both_parts() {
+        assert_eq!(compartment_priority_sum(&EXAMPLE), 157);
+        assert_eq!(badge_priority_sum(&find_badges(&EXAMPLE, 3)), 70);
+    }
+
+    #[test]
+    fn test_badges() {
+        assert_eq!(find_badges(&EXAMPLE, 3), vec![Ok(b'r'), Ok(b'Z')]);
+        assert_eq!(find_badges(&EXAMPLE, 6), vec![Err(BadgeError::NoCommonItem { group: 1 })]);
+        assert_eq!(find_badges(&EXAMPLE[..4], 3), vec![Ok(b'r'), Err(BadgeError::IncompleteGroup { group: 2, size: 1 })]);
+        assert_eq!(find_badges(&["abcD", "xbyD"], 2), vec![Err(BadgeError::MultipleCommonItems { group: 1, items: b"bD".to_vec() })]);
+        assert_eq!(BadgeError::MultipleCommonItems { group: 1, items: b"bD".to_vec() }.to_string(), "group 1: several items carried by every rucksack: bD");
+    }
+
+    #[test]
+    #[should_panic(expected = "at least one rucksack")]
+    fn test_badges_empty_group() {
+        find_badges(&EXAMPLE, 0);
+    }
+
+    #[test]
+    fn test_priority_to_byte() {
+        for character in (b'a'..=b'z').chain(b'A'..=b'Z') {
+            assert_eq!(get_priority_char(get_char_priority(character)), character);
+        }
+    }
+
+    #[test]
+    fn test_validate() {
+        assert_eq!(validate(&EXAMPLE), vec![]);
+
+        let problems = validate(&["ab1b", "abc", "abcd", "a-b"]);
+        assert_eq!(problems, vec![
+            RucksackProblem::InvalidItem { line: 1, item: '1' },
+            RucksackProblem::OddLength { line: 2, length: 3 },
+            RucksackProblem::NoSharedItem { line: 3 },
+            RucksackProblem::InvalidItem { line: 4, item: '-' },
+            RucksackProblem::OddLength { line: 4, length: 3 },
+        ]);
+        assert!(!problems[2].is_fatal());
+        assert_eq!(problems[0].to_string(), "line 1: item '1' is not a letter");
+        assert_eq!(problems[2].to_string(), "line 3: no item in both compartments");
+    }
 }
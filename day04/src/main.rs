@@ -1,6 +1,6 @@
-use std::{fs, str::FromStr};
+use std::{fs, str::FromStr, env, cmp::Reverse, collections::BinaryHeap, fmt::Display, num::ParseIntError};
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone, Copy)]
 struct Range {
     low: u32,
     high: u32
@@ -17,29 +17,293 @@ impl Range {
     }
 }
 
+#[derive(Debug, PartialEq)]
+enum ParseRangeError {
+    /// The line is not two ranges separated by a comma
+    MissingComma,
+    MissingDash,
+    InvalidBound(ParseIntError),
+    /// The low bound is above the high bound
+    Reversed { low: u32, high: u32 }
+}
+
+impl Display for ParseRangeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseRangeError::MissingComma => write!(f, "expected two ranges separated by ','"),
+            ParseRangeError::MissingDash => write!(f, "expected a range like 'a-b'"),
+            ParseRangeError::InvalidBound(error) => write!(f, "invalid bound: {error}"),
+            ParseRangeError::Reversed { low, high } => write!(f, "low bound {low} is above high bound {high}"),
+        }
+    }
+}
+
+impl From<ParseIntError> for ParseRangeError {
+    fn from(error: ParseIntError) -> Self {
+        ParseRangeError::InvalidBound(error)
+    }
+}
+
 impl FromStr for Range {
-    type Err = std::num::ParseIntError;
+    type Err = ParseRangeError;
 
     // Expects a-b
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let (low_str, high_str) = s.split_once('-').unwrap();
+        let (low_str, high_str) = s.trim().split_once('-').ok_or(ParseRangeError::MissingDash)?;
 
-        let low = low_str.parse().unwrap();
-        let high = high_str.parse().unwrap();
+        let low = low_str.parse()?;
+        let high = high_str.parse()?;
+
+        if low > high {
+            return Err(ParseRangeError::Reversed { low, high });
+        }
 
         Ok(Range {low, high})
     }
 }
 
+/// Parses a line like a-b,c-d
+fn parse_pair(line: &str) -> Result<(Range, Range), ParseRangeError> {
+    let (first_range, second_range) = line.split_once(',').ok_or(ParseRangeError::MissingComma)?;
+
+    Ok((first_range.parse()?, second_range.parse()?))
+}
+
+/// Sorted set of disjoint, non-adjacent ranges
+#[derive(Debug, PartialEq, Default)]
+struct RangeSet {
+    ranges: Vec<Range>
+}
+
+impl RangeSet {
+    /// Normalizes any ranges into a set, merging overlapping and adjacent ones
+    fn build(ranges: impl IntoIterator<Item = Range>) -> RangeSet {
+        let mut ranges: Vec<Range> = ranges.into_iter().collect();
+        ranges.sort_by_key(|range| range.low);
+
+        let mut merged: Vec<Range> = Vec::with_capacity(ranges.len());
+
+        for range in ranges {
+            match merged.last_mut() {
+                Some(last) if range.low <= last.high.saturating_add(1) => last.high = last.high.max(range.high),
+                _ => merged.push(range),
+            }
+        }
+
+        RangeSet { ranges: merged }
+    }
+
+    fn union(&self, other: &RangeSet) -> RangeSet {
+        RangeSet::build(self.ranges.iter().chain(&other.ranges).copied())
+    }
+
+    fn intersection(&self, other: &RangeSet) -> RangeSet {
+        let mut ranges = Vec::new();
+        let (mut i, mut j) = (0, 0);
+
+        while i < self.ranges.len() && j < other.ranges.len() {
+            let (a, b) = (self.ranges[i], other.ranges[j]);
+
+            let low = a.low.max(b.low);
+            let high = a.high.min(b.high);
+            if low <= high {
+                ranges.push(Range { low, high });
+            }
+
+            // Advance whichever ends first, the other may still overlap the next range
+            if a.high < b.high {
+                i += 1;
+            } else {
+                j += 1;
+            }
+        }
+
+        RangeSet { ranges }
+    }
+
+    fn difference(&self, other: &RangeSet) -> RangeSet {
+        let mut ranges = Vec::new();
+        let mut j = 0;
+
+        for range in &self.ranges {
+            // Skip the ranges of other that lie entirely before this one
+            while j < other.ranges.len() && other.ranges[j].high < range.low {
+                j += 1;
+            }
+
+            // Start of the part of this range that is not cut away yet
+            let mut low = Some(range.low);
+            let mut k = j;
+
+            while let Some(start) = low {
+                match other.ranges.get(k) {
+                    Some(cut) if cut.low <= range.high => {
+                        if cut.low > start {
+                            ranges.push(Range { low: start, high: cut.low - 1 });
+                        }
+                        low = if cut.high < range.high { Some(cut.high + 1) } else { None };
+                        k += 1;
+                    }
+                    _ => {
+                        ranges.push(Range { low: start, high: range.high });
+                        low = None;
+                    }
+                }
+            }
+        }
+
+        RangeSet { ranges }
+    }
+
+    /// Number of sections in the set
+    fn len(&self) -> u64 {
+        self.ranges.iter().map(|range| (range.high - range.low) as u64 + 1).sum()
+    }
+
+    fn contains(&self, section: u32) -> bool {
+        match self.ranges.binary_search_by(|range| range.low.cmp(&section)) {
+            Ok(_) => true,
+            Err(0) => false,
+            Err(index) => self.ranges[index - 1].high >= section,
+        }
+    }
+
+    /// The smallest range holding the whole set
+    fn hull(&self) -> Option<Range> {
+        Some(Range { low: self.ranges.first()?.low, high: self.ranges.last()?.high })
+    }
+}
+
+/// The assignment of a single elf, `line` is numbered from 1 and `elf` is 0 or 1 within the line
+#[derive(Debug, PartialEq, Clone, Copy)]
+struct Assignment {
+    line: usize,
+    elf: usize,
+    range: Range
+}
+
+impl Display for Assignment {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.line, self.elf)
+    }
+}
+
+/// Splits the hull of all assignments into maximal ranges covered by the same number of elves
+fn coverage(assignments: &[Assignment]) -> Vec<(Range, usize)> {
+    // Coverage changes at the start of a range and right after its end
+    let mut events: Vec<(u64, isize)> = assignments.iter()
+        .flat_map(|assignment| [(assignment.range.low as u64, 1), (assignment.range.high as u64 + 1, -1)])
+        .collect();
+    events.sort_unstable();
+
+    let mut segments: Vec<(Range, usize)> = Vec::new();
+    let mut count = 0;
+
+    for (index, (section, change)) in events.iter().enumerate() {
+        count += change;
+
+        // Only close a segment after all changes at this section are applied
+        if let Some((next, _)) = events.get(index + 1) {
+            if next > section {
+                let range = Range { low: *section as u32, high: (next - 1) as u32 };
+
+                // One elf ending where another starts leaves the count unchanged
+                match segments.last_mut() {
+                    Some((last, last_count)) if *last_count == count as usize => last.high = range.high,
+                    _ => segments.push((range, count as usize)),
+                }
+            }
+        }
+    }
+
+    segments
+}
+
+/// Calls `visit` with every pair of elves whose assignments overlap, as indices into `assignments`,
+/// lower index first. Sweeps the assignments by start, keeping the ones still running, so it
+/// takes O(n log n + pairs) without holding the pairs.
+fn for_each_overlapping_pair(assignments: &[Assignment], mut visit: impl FnMut(usize, usize)) {
+    let mut order: Vec<usize> = (0..assignments.len()).collect();
+    order.sort_by_key(|&index| assignments[index].range.low);
+
+    // Running assignments, ending first on top
+    let mut active: BinaryHeap<Reverse<(u32, usize)>> = BinaryHeap::new();
+
+    for index in order {
+        let range = assignments[index].range;
+
+        while let Some(Reverse((high, _))) = active.peek() {
+            if *high >= range.low {
+                break;
+            }
+            active.pop();
+        }
+
+        for Reverse((_, other)) in active.iter() {
+            visit(*other.min(&index), *other.max(&index));
+        }
+
+        active.push(Reverse((range.high, index)));
+    }
+}
+
+/// Prints the coverage of all elves, listing at most `max_pairs` of the pairs overlapping
+/// across lines, or all of them when `None`
+fn print_coverage_report(assignments: &[Assignment], max_pairs: Option<usize>) {
+    let segments = coverage(assignments);
+
+    println!("Coverage per section:");
+    for (range, count) in &segments {
+        println!("  {}-{}: {count} elves", range.low, range.high);
+    }
+
+    let gaps: Vec<_> = segments.iter().filter(|(_, count)| *count == 0).map(|(range, _)| range).collect();
+    println!("Uncovered gaps: {:?}", gaps);
+
+    if let Some(most) = segments.iter().map(|(_, count)| *count).max() {
+        let busiest: Vec<_> = segments.iter().filter(|(_, count)| *count == most).map(|(range, _)| range).collect();
+        println!("Most over-assigned ({most} elves): {:?}", busiest);
+    }
+
+    // Only the listed pairs are kept, the real input has over a million
+    let mut pairs = 0;
+    let mut across_lines = 0;
+    let mut shown = Vec::new();
+    for_each_overlapping_pair(assignments, |a, b| {
+        pairs += 1;
+        if assignments[a].line != assignments[b].line {
+            across_lines += 1;
+            if max_pairs.is_none_or(|max_pairs| shown.len() < max_pairs) {
+                shown.push((a, b));
+            }
+        }
+    });
+    println!("Overlapping pairs: {pairs} ({across_lines} across lines)");
+
+    for (a, b) in &shown {
+        println!("  {} overlaps {}", assignments[*a], assignments[*b]);
+    }
+    if shown.len() < across_lines {
+        println!("  ... {} more, pass `all-pairs` to list them all", across_lines - shown.len());
+    }
+}
+
 fn main() {
     let input: String = fs::read_to_string("input.txt").unwrap();
 
     let mut contained = 0;
     let mut overlap = 0;
+    let mut shared_ranges = Vec::new();
+    let mut assignments = Vec::new();
 
-    for line in input.lines() {
-        let (first_range, second_range) = line.split_once(',').unwrap();
-        let (first_range, second_range) : (Range, Range) = (first_range.parse().unwrap(), second_range.parse().unwrap());
+    for (index, line) in input.lines().enumerate() {
+        let (first_range, second_range) = match parse_pair(line) {
+            Ok(pair) => pair,
+            Err(error) => {
+                println!("Skipping line {}: {error} in {line:?}", index + 1);
+                continue;
+            }
+        };
 
         if first_range.contains(&second_range) || second_range.contains(&first_range) {
             contained += 1;
@@ -48,7 +312,128 @@ fn main() {
         if first_range.overlap(&second_range) || second_range.overlap(&first_range) {
             overlap += 1;
         }
+
+        let shared_low = first_range.low.max(second_range.low);
+        let shared_high = first_range.high.min(second_range.high);
+        if shared_low <= shared_high {
+            shared_ranges.push(Range { low: shared_low, high: shared_high });
+        }
+
+        assignments.push(Assignment { line: index + 1, elf: 0, range: first_range });
+        assignments.push(Assignment { line: index + 1, elf: 1, range: second_range });
     }
 
     println!("Contained: {contained}, Overlap: {overlap}");
+
+    let first_elves = RangeSet::build(assignments.iter().filter(|assignment| assignment.elf == 0).map(|assignment| assignment.range));
+    let second_elves = RangeSet::build(assignments.iter().filter(|assignment| assignment.elf == 1).map(|assignment| assignment.range));
+    let covered = first_elves.union(&second_elves);
+    let shared = RangeSet::build(shared_ranges);
+
+    if let Some(hull) = covered.hull() {
+        let uncovered = RangeSet::build([hull]).difference(&covered);
+        let both_sides = first_elves.intersection(&second_elves);
+        println!(
+            "Sections {}-{}: {} covered, {} by first and second elves, {} shared within a pair, uncovered: {:?}",
+            hull.low, hull.high, covered.len(), both_sides.len(), shared.len(), uncovered.ranges
+        );
+    }
+
+    // Pass `coverage` for a report over all elves, and `all-pairs` to list every overlapping pair in it
+    if env::args().any(|arg| arg == "coverage") {
+        let max_pairs = if env::args().any(|arg| arg == "all-pairs") { None } else { Some(20) };
+        print_coverage_report(&assignments, max_pairs);
+    }
+
+    // Pass section IDs to ask whether anybody covers them
+    for section in env::args().skip(1).filter_map(|arg| arg.parse().ok()) {
+        println!("Section {section} covered: {}", covered.contains(section));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn set(ranges: &[(u32, u32)]) -> RangeSet {
+        RangeSet::build(ranges.iter().map(|&(low, high)| Range { low, high }))
+    }
+
+    #[test]
+    fn test_build_merges() {
+        assert_eq!(set(&[(5, 7), (1, 2), (3, 4), (6, 9), (12, 12)]).ranges, set(&[(1, 9), (12, 12)]).ranges);
+        assert_eq!(set(&[(1, 9), (12, 12)]).ranges, vec![Range { low: 1, high: 9 }, Range { low: 12, high: 12 }]);
+        assert_eq!(set(&[(0, u32::MAX), (3, 4)]).ranges, vec![Range { low: 0, high: u32::MAX }]);
+    }
+
+    #[test]
+    fn test_set_algebra() {
+        let a = set(&[(1, 5), (10, 20)]);
+        let b = set(&[(4, 12), (18, 30)]);
+
+        assert_eq!(a.union(&b), set(&[(1, 30)]));
+        assert_eq!(a.intersection(&b), set(&[(4, 5), (10, 12), (18, 20)]));
+        assert_eq!(a.difference(&b), set(&[(1, 3), (13, 17)]));
+        assert_eq!(b.difference(&a), set(&[(6, 9), (21, 30)]));
+        assert_eq!(a.difference(&set(&[(0, 40)])), RangeSet::default());
+        assert_eq!(a.difference(&RangeSet::default()), a);
+    }
+
+    #[test]
+    fn test_len_and_contains() {
+        let a = set(&[(1, 5), (10, 20)]);
+
+        assert_eq!(a.len(), 16);
+        assert!(a.contains(1) && a.contains(5) && a.contains(10) && a.contains(20));
+        assert!(!a.contains(0) && !a.contains(7) && !a.contains(21));
+        assert_eq!(a.hull(), Some(Range { low: 1, high: 20 }));
+    }
+
+    fn assignments(ranges: &[(u32, u32)]) -> Vec<Assignment> {
+        ranges.iter().enumerate()
+            .map(|(index, &(low, high))| Assignment { line: index / 2 + 1, elf: index % 2, range: Range { low, high } })
+            .collect()
+    }
+
+    #[test]
+    fn test_coverage() {
+        let elves = assignments(&[(2, 4), (6, 8), (3, 4), (10, 12)]);
+
+        assert_eq!(coverage(&elves), vec![
+            (Range { low: 2, high: 2 }, 1),
+            (Range { low: 3, high: 4 }, 2),
+            (Range { low: 5, high: 5 }, 0),
+            (Range { low: 6, high: 8 }, 1),
+            (Range { low: 9, high: 9 }, 0),
+            (Range { low: 10, high: 12 }, 1),
+        ]);
+        assert_eq!(coverage(&[]), vec![]);
+        assert_eq!(coverage(&assignments(&[(1, 3), (4, 6)])), vec![(Range { low: 1, high: 6 }, 1)]);
+    }
+
+    #[test]
+    fn test_overlapping_pairs() {
+        let elves = assignments(&[(2, 4), (6, 8), (3, 6), (10, 12), (8, 8), (1, 100)]);
+
+        assert_eq!(elves[5].to_string(), "3:1");
+        let mut pairs = Vec::new();
+        for_each_overlapping_pair(&elves, |a, b| pairs.push((a, b)));
+        pairs.sort_unstable();
+        assert_eq!(pairs, vec![
+            (0, 2), (0, 5), (1, 2), (1, 4), (1, 5), (2, 5), (3, 5), (4, 5),
+        ]);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!("2-4".parse(), Ok(Range { low: 2, high: 4 }));
+        assert_eq!("24".parse::<Range>(), Err(ParseRangeError::MissingDash));
+        assert!(matches!("2-x".parse::<Range>(), Err(ParseRangeError::InvalidBound(_))));
+        assert!(matches!("-4".parse::<Range>(), Err(ParseRangeError::InvalidBound(_))));
+        assert_eq!("6-4".parse::<Range>(), Err(ParseRangeError::Reversed { low: 6, high: 4 }));
+
+        assert_eq!(parse_pair("2-4;6-8"), Err(ParseRangeError::MissingComma));
+        assert_eq!(parse_pair("2-4,8-6"), Err(ParseRangeError::Reversed { low: 8, high: 6 }));
+        assert_eq!(parse_pair("2-4,6-8"), Ok((Range { low: 2, high: 4 }, Range { low: 6, high: 8 })));
+    }
 }
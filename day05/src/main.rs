@@ -1,44 +1,233 @@
-use std::{fs, fmt::Debug};
+use std::{fs, fmt::{Debug, Display}, str::FromStr, num::ParseIntError, env, collections::HashMap, hash::{BuildHasher, RandomState}};
 
+/// How the crane moves several crates at once
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum CraneModel {
+    /// Moves crates one at a time, reversing their order
+    CrateMover9000,
+    /// Moves all crates at once, keeping their order
+    CrateMover9001
+}
+
+/// Why a stack drawing could not be parsed, lines are numbered from 1 at the top of the drawing
+#[derive(Debug, PartialEq)]
+enum DrawingError {
+    /// There is no line numbering the stacks
+    Empty,
+    /// The stacks are not numbered 1, 2, 3, ... from left to right
+    BadStackNumber { token: String, expected: usize },
+    /// Text other than a crate like [A] in a crate row
+    UnexpectedText { line: usize, column: usize },
+    /// A crate label without closing bracket or without any text
+    MalformedCrate { line: usize, column: usize },
+    /// A crate that is not above exactly one stack number
+    NotAboveStack { line: usize, column: usize },
+    /// A crate with empty space underneath it
+    FloatingCrate { line: usize, stack: usize }
+}
+
+impl Display for DrawingError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DrawingError::Empty => write!(f, "drawing has no stack numbers"),
+            DrawingError::BadStackNumber { token, expected } => write!(f, "expected stack number {expected}, found {token:?}"),
+            DrawingError::UnexpectedText { line, column } => write!(f, "unexpected text at line {line}, column {column}"),
+            DrawingError::MalformedCrate { line, column } => write!(f, "malformed crate at line {line}, column {column}"),
+            DrawingError::NotAboveStack { line, column } => write!(f, "crate at line {line}, column {column} is not above a single stack"),
+            DrawingError::FloatingCrate { line, stack } => write!(f, "crate at line {line} in stack {stack} has nothing underneath"),
+        }
+    }
+}
+
+/// Finds the whitespace separated tokens of a line with their first and last character column
+fn tokens(line: &str) -> Vec<(usize, usize, String)> {
+    let mut tokens: Vec<(usize, usize, String)> = Vec::new();
+    let mut previous_space = true;
+
+    for (column, character) in line.chars().enumerate() {
+        if character.is_whitespace() {
+            previous_space = true;
+            continue;
+        }
+
+        match tokens.last_mut() {
+            Some((_, end, token)) if !previous_space => {
+                *end = column;
+                token.push(character);
+            }
+            _ => tokens.push((column, column, character.to_string())),
+        }
+        previous_space = false;
+    }
+
+    tokens
+}
+
+/// A single crane instruction, stacks are numbered from 1
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Move {
+    count: usize,
+    from: usize,
+    to: usize
+}
+
+#[derive(Debug, PartialEq)]
+enum ParseMoveError {
+    /// Not of the form move N from A to B
+    Malformed,
+    InvalidNumber(ParseIntError)
+}
+
+impl Display for ParseMoveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseMoveError::Malformed => write!(f, "expected 'move N from A to B'"),
+            ParseMoveError::InvalidNumber(error) => write!(f, "invalid number: {error}"),
+        }
+    }
+}
+
+impl From<ParseIntError> for ParseMoveError {
+    fn from(error: ParseIntError) -> Self {
+        ParseMoveError::InvalidNumber(error)
+    }
+}
+
+impl FromStr for Move {
+    type Err = ParseMoveError;
+
+    // Expects move a from b to c
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let ["move", count, "from", from, "to", to] = s.split_whitespace().collect::<Vec<_>>()[..] else {
+            return Err(ParseMoveError::Malformed);
+        };
+
+        Ok(Move { count: count.parse()?, from: from.parse()?, to: to.parse()? })
+    }
+}
+
+impl Display for Move {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "move {} from {} to {}", self.count, self.from, self.to)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum MoveError {
+    UnknownStack(usize),
+    NotEnoughCrates { stack: usize, available: usize, requested: usize }
+}
+
+impl Display for MoveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MoveError::UnknownStack(stack) => write!(f, "there is no stack {stack}"),
+            MoveError::NotEnoughCrates { stack, available, requested } => {
+                write!(f, "cannot take {requested} crates from stack {stack} holding {available}")
+            }
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Hash)]
 struct Ship {
-    stacks: Vec<Vec<char>>
+    stacks: Vec<Vec<String>>
 }
 
 impl Ship {
-    fn build(input: &str) -> Ship {
-        let mut stacks: Vec<Vec<char>> = Vec::new();
-
-        let mut iter = input.lines().rev();
+    /// Parses the drawing of the stacks, with the stack numbers on the last line. Crates are
+    /// matched to the stack number below them by column, so rows may be trimmed and both crate
+    /// labels and stack numbers may be longer than a single character.
+    fn build(input: &str) -> Result<Ship, DrawingError> {
+        let mut lines: Vec<_> = input.lines().enumerate().filter(|(_, line)| !line.trim().is_empty()).collect();
 
-        let line = iter.next().unwrap();
+        let (_, number_line) = lines.pop().ok_or(DrawingError::Empty)?;
 
-        for _ in line.split_whitespace() {
-            stacks.push(Vec::new());
+        let numbers = tokens(number_line);
+        if numbers.is_empty() {
+            return Err(DrawingError::Empty);
         }
+        for (index, (_, _, token)) in numbers.iter().enumerate() {
+            if token.parse() != Ok(index + 1) {
+                return Err(DrawingError::BadStackNumber { token: token.clone(), expected: index + 1 });
+            }
+        }
+
+        let mut stacks: Vec<Vec<String>> = vec![Vec::new(); numbers.len()];
 
-        for line in iter {
-            for index in 0..stacks.len() {
-                let character = line.chars().nth(1 + 4 * index).unwrap();
+        // Rows from the bottom up, a crate in row `height` rests on `height` crates
+        for (height, (index, line)) in lines.into_iter().rev().enumerate() {
+            let line_number = index + 1;
 
-                if character != ' ' {
-                    stacks[index].push(character);
+            for (start, end, token) in tokens(line) {
+                let column = start + 1;
+
+                if !token.starts_with('[') {
+                    return Err(DrawingError::UnexpectedText { line: line_number, column });
                 }
+                let label = match token.strip_prefix('[').and_then(|token| token.strip_suffix(']')) {
+                    Some(label) if !label.is_empty() && !label.contains(['[', ']']) => label,
+                    _ => return Err(DrawingError::MalformedCrate { line: line_number, column }),
+                };
+
+                let below: Vec<_> = numbers.iter()
+                    .enumerate()
+                    .filter(|(_, (number_start, number_end, _))| *number_start <= end && start <= *number_end)
+                    .map(|(stack, _)| stack)
+                    .collect();
+
+                let [stack] = below[..] else {
+                    return Err(DrawingError::NotAboveStack { line: line_number, column });
+                };
+
+                if stacks[stack].len() > height {
+                    // A second crate above the same stack on this row
+                    return Err(DrawingError::NotAboveStack { line: line_number, column });
+                }
+                if stacks[stack].len() < height {
+                    return Err(DrawingError::FloatingCrate { line: line_number, stack: stack + 1 });
+                }
+
+                stacks[stack].push(label.to_string());
             }
         }
 
-        Ship { stacks }
+        Ok(Ship { stacks })
     }
 
-    fn apply_instruction(&mut self, count: usize, from: usize, to: usize) {
-        let length_from = self.stacks[from - 1].len();
+    /// Applies the move if both stacks exist and the source holds enough crates, otherwise leaves the ship untouched
+    fn apply(&mut self, model: CraneModel, instruction: &Move) -> Result<(), MoveError> {
+        for stack in [instruction.from, instruction.to] {
+            if stack == 0 || stack > self.stacks.len() {
+                return Err(MoveError::UnknownStack(stack));
+            }
+        }
 
-        let mut to_move = self.stacks[from - 1].split_off(length_from - count);
+        let available = self.stacks[instruction.from - 1].len();
+        if available < instruction.count {
+            return Err(MoveError::NotEnoughCrates { stack: instruction.from, available, requested: instruction.count });
+        }
 
-        self.stacks[to - 1].append(&mut to_move);
+        self.apply_instruction(model, instruction.count, instruction.from, instruction.to);
 
-        // for _ in 0..count {
-        //     self.move_container(from, to);
-        // }
+        Ok(())
+    }
+
+    fn apply_instruction(&mut self, model: CraneModel, count: usize, from: usize, to: usize) {
+        match model {
+            CraneModel::CrateMover9000 => {
+                for _ in 0..count {
+                    self.move_container(from, to);
+                }
+            }
+            CraneModel::CrateMover9001 => {
+                let length_from = self.stacks[from - 1].len();
+
+                let mut to_move = self.stacks[from - 1].split_off(length_from - count);
+
+                self.stacks[to - 1].append(&mut to_move);
+            }
+        }
     }
 
     fn move_container(&mut self, from: usize, to: usize) {
@@ -47,11 +236,52 @@ impl Ship {
         self.stacks[to - 1].push(container);
     }
 
-    fn print_top_crates(&self) {
-        for stack in &self.stacks {
-            print!("{:}", stack.last().unwrap());
-        } 
-        println!();
+    fn top_crates(&self) -> String {
+        self.stacks.iter().filter_map(|stack| stack.last().map(String::as_str)).collect()
+    }
+
+    /// Every valid move, except the one undoing `previous`
+    fn moves(&self, previous: Option<Move>) -> impl Iterator<Item = Move> + '_ {
+        let stacks = self.stacks.len();
+
+        (1..=stacks)
+            .flat_map(move |from| (1..=stacks).filter(move |to| *to != from).map(move |to| (from, to)))
+            .flat_map(move |(from, to)| (1..=self.stacks[from - 1].len()).map(move |count| Move { count, from, to }))
+            .filter(move |instruction| previous.is_none_or(|previous| {
+                (previous.count, previous.from, previous.to) != (instruction.count, instruction.to, instruction.from)
+            }))
+    }
+
+    /// The top crates after `instruction`, without moving anything
+    fn top_crates_after(&self, model: CraneModel, instruction: Move) -> impl Iterator<Item = &str> + '_ {
+        let from = &self.stacks[instruction.from - 1];
+        let remaining = from.len() - instruction.count;
+        let moved_top = match model {
+            CraneModel::CrateMover9000 => &from[remaining],
+            CraneModel::CrateMover9001 => &from[from.len() - 1],
+        };
+
+        self.stacks.iter().enumerate().filter_map(move |(index, stack)| match index + 1 {
+            number if number == instruction.from => remaining.checked_sub(1).map(|top| from[top].as_str()),
+            number if number == instruction.to => Some(moved_top.as_str()),
+            _ => stack.last().map(String::as_str),
+        })
+    }
+
+    /// A single move after which the top crates read `target`
+    fn finishing_move(&self, model: CraneModel, target: &str) -> Option<Move> {
+        self.moves(None).find(|instruction| {
+            let mut rest = target;
+            let matches = self.top_crates_after(model, *instruction).all(|top| match rest.strip_prefix(top) {
+                Some(after) => {
+                    rest = after;
+                    true
+                }
+                None => false,
+            });
+
+            matches && rest.is_empty()
+        })
     }
 }
 
@@ -67,16 +297,199 @@ impl Debug for Ship {
                     write!(f, "    ")?;
                 }
             }
-            write!(f, "\n")?;
+            writeln!(f)?;
         }
-        for (index, _) in (&self.stacks).iter().enumerate() {
+        for index in 0..self.stacks.len() {
             write!(f, "  {} ", index + 1)?;
         }
-        write!(f, "\n")?;
+        writeln!(f)?;
         Ok(())
     }
 }
 
+impl Display for Ship {
+    /// Draws the stacks exactly like the puzzle input, so that `Ship::build` parses it back
+    /// into the same ship. Every stack gets a column wide enough for its widest crate and
+    /// number, with crates and numbers centered in it.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let max_height = self.stacks.iter().map(Vec::len).max().unwrap_or(0);
+        let width = self.stacks.iter()
+            .flatten()
+            .map(|name| name.chars().count() + 2)
+            .chain([self.stacks.len().to_string().len()])
+            .max()
+            .unwrap_or(3);
+
+        for row in (0..max_height).rev() {
+            let cells: Vec<_> = self.stacks.iter()
+                .map(|stack| match stack.get(row) {
+                    Some(name) => format!("{:^width$}", format!("[{name}]")),
+                    None => " ".repeat(width),
+                })
+                .collect();
+            writeln!(f, "{}", cells.join(" "))?;
+        }
+
+        let numbers: Vec<_> = (1..=self.stacks.len()).map(|number| format!("{number:^width$}")).collect();
+        writeln!(f, "{}", numbers.join(" "))
+    }
+}
+
+/// Upper bound on the ships `find_plan` keeps in memory
+const PLAN_STATE_LIMIT: usize = 100_000;
+
+#[derive(Debug, PartialEq)]
+enum PlanError {
+    /// No plan reaches the target within the move limit
+    NotFound,
+    /// More than `PLAN_STATE_LIMIT` ships would have to be kept to finish the search
+    SearchLimitReached
+}
+
+impl Display for PlanError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PlanError::NotFound => write!(f, "no plan within the move limit"),
+            PlanError::SearchLimitReached => write!(f, "search limit reached"),
+        }
+    }
+}
+
+/// Searches breadth first for the shortest plan of at most `max_moves` moves after which the
+/// top crates read `target`. The last move of a plan is only looked for, so the ships after
+/// `max_moves - 1` moves are never kept, and the ships after `max_moves` moves never built.
+fn find_plan(ship: &Ship, model: CraneModel, target: &str, max_moves: usize) -> Result<Vec<Move>, PlanError> {
+    if ship.top_crates() == target {
+        return Ok(vec![]);
+    }
+
+    // Every kept ship in breadth first order, with the index of the ship and the move it was first reached from
+    let mut states: Vec<(Ship, Option<(usize, Move)>)> = vec![(ship.clone(), None)];
+    // Indices of the kept ships by their hash, so every ship is only stored in `states`
+    let hasher = RandomState::new();
+    let mut seen: HashMap<u64, Vec<usize>> = HashMap::from([(hasher.hash_one(ship), vec![0])]);
+    let mut layer = 0..1;
+
+    for depth in 0..max_moves {
+        // Plans of `depth + 1` moves, which only differ from the kept ships in their last move
+        for index in layer.clone() {
+            if let Some(last) = states[index].0.finishing_move(model, target) {
+                return Ok(plan_to(&states, index, &[last]));
+            }
+        }
+
+        if depth + 1 == max_moves {
+            break;
+        }
+
+        let keep = depth + 2 < max_moves;
+        let next_layer = states.len();
+
+        for index in layer {
+            let previous = states[index].1.map(|(_, instruction)| instruction);
+
+            let moves: Vec<_> = states[index].0.moves(previous).collect();
+
+            for instruction in moves {
+                let mut next = states[index].0.clone();
+                next.apply_instruction(model, instruction.count, instruction.from, instruction.to);
+
+                let hash = hasher.hash_one(&next);
+                if seen.get(&hash).is_some_and(|indices| indices.iter().any(|kept| states[*kept].0 == next)) {
+                    continue;
+                }
+
+                if !keep {
+                    if let Some(last) = next.finishing_move(model, target) {
+                        return Ok(plan_to(&states, index, &[instruction, last]));
+                    }
+                    continue;
+                }
+
+                if states.len() == PLAN_STATE_LIMIT {
+                    return Err(PlanError::SearchLimitReached);
+                }
+                seen.entry(hash).or_default().push(states.len());
+                states.push((next, Some((index, instruction))));
+            }
+        }
+
+        layer = next_layer..states.len();
+    }
+
+    Err(PlanError::NotFound)
+}
+
+/// The moves leading to the kept ship at `index`, followed by `rest`
+fn plan_to(states: &[(Ship, Option<(usize, Move)>)], mut index: usize, rest: &[Move]) -> Vec<Move> {
+    let mut plan = Vec::new();
+
+    while let Some((parent, instruction)) = states[index].1 {
+        plan.push(instruction);
+        index = parent;
+    }
+
+    plan.reverse();
+    plan.extend_from_slice(rest);
+    plan
+}
+
+/// Steps forward and backward through a crane plan. Every move is its own inverse with the
+/// stacks swapped, for both crane models, so undoing only needs the applied moves.
+struct Replay {
+    ship: Ship,
+    model: CraneModel,
+    moves: Vec<Move>,
+    /// Outcome of every move up to the current step, skipped moves did not change the ship
+    history: Vec<Result<(), MoveError>>
+}
+
+impl Replay {
+    fn new(ship: Ship, model: CraneModel, moves: Vec<Move>) -> Replay {
+        Replay { ship, model, moves, history: Vec::new() }
+    }
+
+    /// Number of moves behind the current state
+    fn step(&self) -> usize {
+        self.history.len()
+    }
+
+    fn ship(&self) -> &Ship {
+        &self.ship
+    }
+
+    /// Applies the next move, returns `None` at the end of the plan. A move that cannot be
+    /// applied is skipped, but still counts as a step.
+    fn step_forward(&mut self) -> Option<&Result<(), MoveError>> {
+        let instruction = self.moves.get(self.step())?;
+
+        let result = self.ship.apply(self.model, instruction);
+        self.history.push(result);
+
+        self.history.last()
+    }
+
+    /// Undoes the last move, returns `None` at the start of the plan
+    fn step_back(&mut self) -> Option<&Move> {
+        let result = self.history.pop()?;
+        let instruction = &self.moves[self.step()];
+
+        if result.is_ok() {
+            self.ship.apply_instruction(self.model, instruction.count, instruction.to, instruction.from);
+        }
+
+        Some(instruction)
+    }
+
+    /// Steps forward or backward until `step` moves are behind, or the plan ends
+    fn seek(&mut self, step: usize) -> &Ship {
+        while self.step() > step && self.step_back().is_some() {}
+        while self.step() < step && self.step_forward().is_some() {}
+
+        &self.ship
+    }
+}
+
 fn main() {
     let input = fs::read_to_string("input.txt").unwrap();
 
@@ -85,36 +498,291 @@ fn main() {
 
     let ship_input = &input[..=space_index];
 
-    let mut ship = Ship::build(ship_input);
+    let ship = match Ship::build(ship_input) {
+        Ok(ship) => ship,
+        Err(error) => {
+            println!("Invalid stack drawing: {error}");
+            return;
+        }
+    };
 
     let instructions = &input[(space_index + 2)..];
-    let instructions = instructions.lines();
+    // Line number of the first instruction in the input
+    let first_line = input[..(space_index + 2)].lines().count() + 1;
+
+    let mut moves = Vec::new();
+    for (index, instruction) in instructions.lines().enumerate() {
+        if instruction.trim().is_empty() {
+            continue;
+        }
 
-    println!("START:\n{:?}", ship);
-    for (index, instruction) in instructions.enumerate() {
-        // Move a from b to c
-        if let [_, count, _, from, _, to] = instruction.split_whitespace().collect::<Vec<_>>()[..] {
-            ship.apply_instruction(count.parse().unwrap(), from.parse().unwrap(), to.parse().unwrap());
+        match instruction.parse::<Move>() {
+            Ok(instruction) => moves.push((first_line + index, instruction)),
+            Err(error) => println!("Skipping line {}: {error} in {instruction:?}", first_line + index),
         }
-        // println!("after {instruction}:\n{:?}", ship);
     }
 
-    ship.print_top_crates();
+    // Pass `replay` to draw the ship after every move, or `replay N` to draw it after move N
+    let args: Vec<String> = env::args().skip(1).collect();
+    let replay_step = match &args[..] {
+        [mode] if mode == "replay" => Some(None),
+        [mode, step] if mode == "replay" => match step.parse() {
+            Ok(step) => Some(Some(step)),
+            Err(_) => {
+                println!("Invalid replay step {step:?}, expected the number of moves to replay");
+                return;
+            }
+        },
+        _ => None,
+    };
+
+    println!("START:\n{}", ship);
+
+    // Pass `plan TARGET [MAX_MOVES]` to search the shortest plan from the start to the given top crates
+    if let [mode, target, rest @ ..] = &args[..] {
+        if mode == "plan" {
+            let max_moves = rest.first().and_then(|max_moves| max_moves.parse().ok()).unwrap_or(3);
+
+            for model in [CraneModel::CrateMover9000, CraneModel::CrateMover9001] {
+                match find_plan(&ship, model, target, max_moves) {
+                    Ok(plan) => {
+                        println!("{:?} reaches {target} in {} moves:", model, plan.len());
+                        for instruction in plan {
+                            println!("{instruction}");
+                        }
+                    }
+                    Err(PlanError::NotFound) => println!("{:?} cannot reach {target} within {max_moves} moves", model),
+                    Err(error) => println!("{:?} gave up on {target}: {error}", model),
+                }
+            }
+            return;
+        }
+    }
+
+    for model in [CraneModel::CrateMover9000, CraneModel::CrateMover9001] {
+        let mut replay = Replay::new(ship.clone(), model, moves.iter().map(|(_, instruction)| *instruction).collect());
+
+        if let Some(Some(step)) = replay_step {
+            println!("{:?} after move {}:\n{}", model, step, replay.seek(step));
+            continue;
+        }
+
+        for (line, instruction) in &moves {
+            if let Some(Err(error)) = replay.step_forward() {
+                println!("{:?} skipping line {line}: {error}", model);
+            }
+            if replay_step.is_some() {
+                println!("after {instruction}:\n{}", replay.ship());
+            }
+        }
+
+        println!("{:?}: {}", model, replay.ship().top_crates());
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn crates(labels: &[&str]) -> Vec<String> {
+        labels.iter().map(|label| label.to_string()).collect()
+    }
+
+    const EXAMPLE: &str = "    [D]    \n[N] [C]    \n[Z] [M] [P]\n 1   2   3 \n";
+
+    #[test]
+    fn test_build() {
+        let ship = Ship::build(EXAMPLE).unwrap();
+
+        assert_eq!(ship.stacks, vec![crates(&["Z", "N"]), crates(&["M", "C", "D"]), crates(&["P"])]);
+        assert_eq!(ship.top_crates(), "NDP");
+    }
+
+    #[test]
+    fn test_build_trimmed_and_wide() {
+        // Editors strip the trailing whitespace of short rows
+        let trimmed = "    [D]\n[N] [C]\n[Z] [M] [P]\n 1   2   3";
+        assert_eq!(Ship::build(trimmed).unwrap(), Ship::build(EXAMPLE).unwrap());
+
+        let wide = concat!(
+            "                                        [K]\n",
+            "[A] [B] [C] [D] [E] [F] [G] [H] [I] [J] [L]\n",
+            " 1   2   3   4   5   6   7   8   9   10  11\n",
+        );
+        let ship = Ship::build(wide).unwrap();
+        assert_eq!(ship.stacks.len(), 11);
+        assert_eq!(ship.stacks[10], crates(&["L", "K"]));
+
+        let labels = "[AB]      [CD]\n[EF] [GH] [IJ]\n  1    2    3";
+        let ship = Ship::build(labels).unwrap();
+        assert_eq!(ship.stacks, vec![crates(&["EF", "AB"]), crates(&["GH"]), crates(&["IJ", "CD"])]);
+    }
+
+    #[test]
+    fn test_build_errors() {
+        assert_eq!(Ship::build("").err(), Some(DrawingError::Empty));
+        assert_eq!(Ship::build("[A]\n 1   3").err(), Some(DrawingError::BadStackNumber { token: "3".to_string(), expected: 2 }));
+        assert_eq!(Ship::build("[A] B\n 1   2").err(), Some(DrawingError::UnexpectedText { line: 1, column: 5 }));
+        assert_eq!(Ship::build("[A] [B\n 1   2").err(), Some(DrawingError::MalformedCrate { line: 1, column: 5 }));
+        assert_eq!(Ship::build("[A] [] \n 1   2").err(), Some(DrawingError::MalformedCrate { line: 1, column: 5 }));
+        assert_eq!(Ship::build("  [A]\n 1   2").err(), Some(DrawingError::NotAboveStack { line: 1, column: 3 }));
+        assert_eq!(Ship::build("        [A]\n 1   2").err(), Some(DrawingError::NotAboveStack { line: 1, column: 9 }));
+        assert_eq!(Ship::build("[A]\n    [B]\n 1   2").err(), Some(DrawingError::FloatingCrate { line: 1, stack: 1 }));
+    }
+
     #[test]
     fn test_apply_instruction() {
         let mut ship = Ship {
-            stacks: vec![vec!['A', 'B'], vec![]]
+            stacks: vec![crates(&["A", "B"]), vec![]]
+        };
+
+        ship.apply_instruction(CraneModel::CrateMover9001, 2, 1, 2);
+
+        assert_eq!(ship.stacks, vec![vec![], crates(&["A", "B"])]);
+
+        ship.apply_instruction(CraneModel::CrateMover9000, 2, 2, 1);
+
+        assert_eq!(ship.stacks, vec![crates(&["B", "A"]), vec![]]);
+    }
+
+    #[test]
+    fn test_parse_move() {
+        assert_eq!("move 1 from 2 to 1".parse(), Ok(Move { count: 1, from: 2, to: 1 }));
+        assert_eq!("move 1 from 2".parse::<Move>(), Err(ParseMoveError::Malformed));
+        assert_eq!("shift 1 from 2 to 1".parse::<Move>(), Err(ParseMoveError::Malformed));
+        assert!(matches!("move x from 2 to 1".parse::<Move>(), Err(ParseMoveError::InvalidNumber(_))));
+    }
+
+    #[test]
+    fn test_checked_apply() {
+        let mut ship = Ship::build(EXAMPLE).unwrap();
+
+        assert_eq!(ship.apply(CraneModel::CrateMover9000, &Move { count: 1, from: 4, to: 1 }), Err(MoveError::UnknownStack(4)));
+        assert_eq!(ship.apply(CraneModel::CrateMover9000, &Move { count: 1, from: 1, to: 0 }), Err(MoveError::UnknownStack(0)));
+        assert_eq!(
+            ship.apply(CraneModel::CrateMover9001, &Move { count: 2, from: 3, to: 1 }),
+            Err(MoveError::NotEnoughCrates { stack: 3, available: 1, requested: 2 })
+        );
+        assert_eq!(ship, Ship::build(EXAMPLE).unwrap());
+
+        assert_eq!(ship.apply(CraneModel::CrateMover9000, &Move { count: 3, from: 2, to: 3 }), Ok(()));
+        assert_eq!(ship.top_crates(), "NM");
+    }
+
+    #[test]
+    fn test_replay() {
+        let ship = Ship::build(EXAMPLE).unwrap();
+        let moves = vec![
+            Move { count: 1, from: 2, to: 1 },
+            Move { count: 3, from: 1, to: 3 },
+            Move { count: 9, from: 1, to: 2 },
+            Move { count: 2, from: 2, to: 1 },
+            Move { count: 1, from: 1, to: 2 },
+        ];
+
+        let expected = [
+            (CraneModel::CrateMover9000, ["NDP", "DCP", "CZ", "CZ", "MZ", "CMZ"]),
+            (CraneModel::CrateMover9001, ["NDP", "DCP", "CD", "CD", "CD", "MCD"]),
+        ];
+
+        for (model, tops) in expected {
+            let mut replay = Replay::new(ship.clone(), model, moves.clone());
+
+            for top in &tops[1..] {
+                assert!(replay.step_forward().is_some());
+                assert_eq!(&replay.ship().top_crates(), top);
+            }
+            assert_eq!(replay.step_forward(), None);
+            assert_eq!(replay.history[2], Err(MoveError::NotEnoughCrates { stack: 1, available: 0, requested: 9 }));
+
+            for top in tops[..5].iter().rev() {
+                assert!(replay.step_back().is_some());
+                assert_eq!(&replay.ship().top_crates(), top);
+            }
+            assert_eq!(replay.step_back(), None);
+            assert!(replay.ship() == &ship);
+
+            assert_eq!(replay.seek(4).top_crates(), tops[4]);
+            assert_eq!(replay.seek(1).top_crates(), tops[1]);
+            assert_eq!(replay.seek(100).top_crates(), tops[5]);
+        }
+    }
+
+    #[test]
+    fn test_display_round_trip() {
+        let ship = Ship::build(EXAMPLE).unwrap();
+
+        assert_eq!(ship.to_string(), EXAMPLE);
+
+        let input = fs::read_to_string("input.txt").unwrap();
+        let drawing = &input[..=input.find("\n\n").unwrap()];
+        assert_eq!(Ship::build(drawing).unwrap().to_string(), drawing);
+    }
+
+    #[test]
+    fn test_display_round_trip_generated() {
+        // Small linear congruential generator, so the generated ships are the same every run
+        let mut seed: u64 = 2022;
+        let mut next = |bound: u64| {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            (seed >> 33) % bound
         };
 
-        ship.apply_instruction(2, 1, 2);
+        for _ in 0..200 {
+            let stack_count = 1 + next(14) as usize;
+            let label_length = 1 + next(3) as usize;
+
+            let stacks: Vec<Vec<String>> = (0..stack_count)
+                .map(|_| (0..next(6)).map(|_| (0..label_length).map(|_| (b'A' + next(26) as u8) as char).collect()).collect())
+                .collect();
+            let ship = Ship { stacks };
+
+            assert!(Ship::build(&ship.to_string()).unwrap() == ship, "round trip failed for\n{}", ship);
+        }
+    }
+
+    #[test]
+    fn test_find_plan() {
+        let ship = Ship::build(EXAMPLE).unwrap();
+
+        assert_eq!(find_plan(&ship, CraneModel::CrateMover9000, "NDP", 3), Ok(vec![]));
+        assert_eq!(find_plan(&ship, CraneModel::CrateMover9000, "CMZ", 1), Err(PlanError::NotFound));
+
+        for model in [CraneModel::CrateMover9000, CraneModel::CrateMover9001] {
+            assert_eq!(find_plan(&ship, model, "NCD", 3), Ok(vec![Move { count: 1, from: 2, to: 3 }]));
+        }
+
+        // Moving two crates at once keeps their order, only the 9001 can do it in one move
+        assert_eq!(find_plan(&ship, CraneModel::CrateMover9001, "NMD", 1), Ok(vec![Move { count: 2, from: 2, to: 3 }]));
+        assert_eq!(find_plan(&ship, CraneModel::CrateMover9000, "NMD", 1), Err(PlanError::NotFound));
+
+        let plan = find_plan(&ship, CraneModel::CrateMover9000, "NMD", 3).unwrap();
+        assert!(plan.len() > 1);
+        let mut replay = Replay::new(ship.clone(), CraneModel::CrateMover9000, plan.clone());
+        assert_eq!(replay.seek(plan.len()).top_crates(), "NMD");
+    }
+
+    #[test]
+    fn test_find_plan_real_drawing() {
+        let input = fs::read_to_string("input.txt").unwrap();
+        let ship = Ship::build(&input[..=input.find("\n\n").unwrap()]).unwrap();
+
+        // Swapping the tops of two stacks needs a third stack to park one of them
+        let plan = find_plan(&ship, CraneModel::CrateMover9001, "PVTDBTLWG", 3).unwrap();
+        assert_eq!(plan.len(), 3);
+        let mut replay = Replay::new(ship.clone(), CraneModel::CrateMover9001, plan.clone());
+        assert_eq!(replay.seek(plan.len()).top_crates(), "PVTDBTLWG");
+        assert_eq!(find_plan(&ship, CraneModel::CrateMover9001, "PVTDBTLWG", 2), Err(PlanError::NotFound));
+
+        assert_eq!(find_plan(&ship, CraneModel::CrateMover9000, "SSS", 4), Err(PlanError::SearchLimitReached));
+    }
+
+    #[test]
+    fn test_display_move() {
+        let instruction = Move { count: 3, from: 1, to: 12 };
 
-        assert_eq!(ship.stacks, vec![vec![], vec!['A', 'B']]);
+        assert_eq!(instruction.to_string(), "move 3 from 1 to 12");
+        assert_eq!(instruction.to_string().parse(), Ok(instruction));
     }
 
     #[test]
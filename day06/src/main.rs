@@ -1,33 +1,225 @@
-use std::fs;
+use std::{fs, io::{self, BufReader, Read}, collections::VecDeque, env};
 
-fn get_end_unique_window(input: &str, window_size: usize) -> usize {
-    for (index, window) in input.as_bytes().windows(window_size).enumerate() {
-        let mut window = window.to_vec();
-        window.sort(); 
-        window.dedup();
-        
-        if window.len() == window_size {
-            return index + window_size;
+/// Sliding window over the last `size` bytes of a stream. Keeps a count per byte value and
+/// the number of values occurring more than once, so every byte is handled in constant time.
+struct UniqueWindow {
+    size: usize,
+    window: VecDeque<u8>,
+    counts: [usize; 256],
+    repeated: usize
+}
+
+impl UniqueWindow {
+    fn new(size: usize) -> UniqueWindow {
+        UniqueWindow { size, window: VecDeque::with_capacity(size + 1), counts: [0; 256], repeated: 0 }
+    }
+
+    /// Pushes the next byte, returns whether the window is full and holds no byte twice
+    fn push(&mut self, byte: u8) -> bool {
+        self.window.push_back(byte);
+        self.counts[byte as usize] += 1;
+        if self.counts[byte as usize] == 2 {
+            self.repeated += 1;
+        }
+
+        if self.window.len() > self.size {
+            let oldest = self.window.pop_front().unwrap() as usize;
+
+            self.counts[oldest] -= 1;
+            if self.counts[oldest] == 1 {
+                self.repeated -= 1;
+            }
+        }
+
+        self.window.len() == self.size && self.repeated == 0
+    }
+}
+
+/// Scans a stream for the first `window_size` distinct bytes in a row and returns the number
+/// of bytes read up to and including them, or `None` if the stream has no such window
+fn get_end_unique_window_in<R: Read>(reader: R, window_size: usize) -> io::Result<Option<usize>> {
+    let mut window = UniqueWindow::new(window_size);
+
+    for (index, byte) in BufReader::new(reader).bytes().enumerate() {
+        if window.push(byte?) {
+            return Ok(Some(index + 1));
         }
     }
 
-    input.as_bytes().len()
+    Ok(None)
+}
+
+fn get_end_unique_window(input: &[u8], window_size: usize) -> Option<usize> {
+    // Reading from memory cannot fail
+    get_end_unique_window_in(input, window_size).unwrap()
+}
+
+/// Which markers of each window size a scan reports
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Occurrence {
+    First,
+    All,
+    /// The nth marker, counting from 1
+    Nth(usize)
+}
+
+/// Scans a stream once for markers of several window sizes at the same time. Returns per
+/// window size the end of every selected window of distinct bytes, in the same order as
+/// `window_sizes`. Overlapping windows count as separate markers.
+fn scan_markers<R: Read>(reader: R, window_sizes: &[usize], occurrence: Occurrence) -> io::Result<Vec<Vec<usize>>> {
+    let mut windows: Vec<_> = window_sizes.iter().map(|size| UniqueWindow::new(*size)).collect();
+    let mut seen = vec![0; window_sizes.len()];
+    let mut markers = vec![Vec::new(); window_sizes.len()];
+
+    let wanted = match occurrence {
+        Occurrence::First => 1,
+        Occurrence::All => usize::MAX,
+        Occurrence::Nth(n) => n,
+    };
+
+    for (index, byte) in BufReader::new(reader).bytes().enumerate() {
+        let byte = byte?;
+
+        for (size, window) in windows.iter_mut().enumerate() {
+            if !window.push(byte) || seen[size] >= wanted {
+                continue;
+            }
+
+            seen[size] += 1;
+            if occurrence == Occurrence::All || seen[size] == wanted {
+                markers[size].push(index + 1);
+            }
+        }
+
+        // Nothing left to find
+        if seen.iter().all(|count| *count >= wanted) {
+            break;
+        }
+    }
+
+    Ok(markers)
+}
+
+/// Parses a count or window size given on the command line, which has to be at least 1
+fn parse_positive(arg: &str) -> Option<usize> {
+    arg.parse().ok().filter(|n| *n > 0)
+}
+
+/// Parses the `first|all|N SIZE...` arguments of a scan, `None` when they are not valid
+fn parse_scan_args(args: &[String]) -> Option<(Occurrence, Vec<usize>)> {
+    let (occurrence, sizes) = args.split_first()?;
+
+    let occurrence = match occurrence.as_str() {
+        "first" => Occurrence::First,
+        "all" => Occurrence::All,
+        n => Occurrence::Nth(parse_positive(n)?),
+    };
+    let sizes: Vec<usize> = sizes.iter().map(|size| parse_positive(size)).collect::<Option<_>>()?;
+
+    if sizes.is_empty() {
+        return None;
+    }
+
+    Some((occurrence, sizes))
+}
+
+/// A payload following a marker, `offset` is the position of its first byte in the datastream
+#[derive(Debug, PartialEq)]
+struct Frame<'a> {
+    marker_offset: usize,
+    offset: usize,
+    payload: &'a [u8]
+}
+
+/// Splits a datastream into the payloads between successive markers. Each marker is searched
+/// for after the end of the previous one, and a payload runs until the next marker starts or
+/// the datastream ends. Bytes before the first marker belong to no frame.
+struct Frames<'a> {
+    input: &'a [u8],
+    window_size: usize,
+    /// Where the search for the next marker starts
+    position: usize
+}
+
+impl<'a> Frames<'a> {
+    fn new(input: &'a [u8], window_size: usize) -> Frames<'a> {
+        Frames { input, window_size, position: 0 }
+    }
+}
+
+impl<'a> Iterator for Frames<'a> {
+    type Item = Frame<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let offset = self.position + get_end_unique_window(&self.input[self.position..], self.window_size)?;
+
+        let end = match get_end_unique_window(&self.input[offset..], self.window_size) {
+            Some(next_marker_end) => offset + next_marker_end - self.window_size,
+            None => self.input.len(),
+        };
+        self.position = end;
+
+        Some(Frame { marker_offset: offset - self.window_size, offset, payload: &self.input[offset..end] })
+    }
 }
 
-fn get_start_of_packet_marker(input: &str) -> usize {
-    get_end_unique_window(input, 4)
+/// Trailing whitespace like the final newline of the input file is not part of the datastream,
+/// so it cannot complete a marker
+fn get_start_of_packet_marker(input: &str) -> Option<usize> {
+    get_end_unique_window(input.trim_end().as_bytes(), 4)
 }
 
-fn get_start_of_message_marker(input: &str) -> usize {
-    get_end_unique_window(input, 14)
+fn get_start_of_message_marker(input: &str) -> Option<usize> {
+    get_end_unique_window(input.trim_end().as_bytes(), 14)
 }
 
 fn main() {
     let input: String = fs::read_to_string("input.txt").unwrap();
 
-    let result = get_start_of_message_marker(&input);
+    match get_start_of_packet_marker(&input) {
+        Some(marker) => println!("Start of packet: {marker}"),
+        None => println!("Datastream contains no start-of-packet marker"),
+    }
+    match get_start_of_message_marker(&input) {
+        Some(marker) => println!("Start of message: {marker}"),
+        None => println!("Datastream contains no start-of-message marker"),
+    }
+
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    // Pass `frames SIZE` to split the datastream into the payloads between markers
+    if let [mode, frame_args @ ..] = &args[..] {
+        if mode == "frames" {
+            let size = match frame_args {
+                [size] => parse_positive(size),
+                _ => None,
+            };
+            let Some(size) = size else {
+                println!("Usage: frames SIZE, with SIZE at least 1");
+                return;
+            };
 
-    println!("{result}");
+            for frame in Frames::new(input.trim_end().as_bytes(), size) {
+                println!("Frame at {} (marker at {}): {:?}", frame.offset, frame.marker_offset, String::from_utf8_lossy(frame.payload));
+            }
+        }
+    }
+
+    // Pass `scan first|all|N SIZE...` to list the markers of several window sizes
+    if let [mode, scan_args @ ..] = &args[..] {
+        if mode == "scan" {
+            let Some((occurrence, sizes)) = parse_scan_args(scan_args) else {
+                println!("Usage: scan first|all|N SIZE..., with N and every SIZE at least 1");
+                return;
+            };
+
+            // Reading from memory cannot fail
+            let markers = scan_markers(input.trim_end().as_bytes(), &sizes, occurrence).unwrap();
+            for (size, markers) in sizes.iter().zip(markers) {
+                println!("Window {size}: {} markers {:?}", markers.len(), markers);
+            }
+        }
+    }
 }
 
 #[cfg(test)]
@@ -39,13 +231,119 @@ mod tests {
     fn test_part_one() {
         let input = "bvwbjplbgvbhsrlpgdmjqwftvncz";
 
-        assert_eq!(get_start_of_packet_marker(input), 5);
+        assert_eq!(get_start_of_packet_marker(input), Some(5));
     }
 
     #[test]
     fn test_part_two() {
         let input = "mjqjpqmgbljsphdztnvjfqwrcgsmlb";
 
-        assert_eq!(get_start_of_message_marker(input), 19);
+        assert_eq!(get_start_of_message_marker(input), Some(19));
+    }
+
+    #[test]
+    fn test_examples() {
+        let examples = [
+            ("mjqjpqmgbljsphdztnvjfqwrcgsmlb", 7, 19),
+            ("bvwbjplbgvbhsrlpgdmjqwftvncz", 5, 23),
+            ("nppdvjthqldpwncqszvftbrmjlhg", 6, 23),
+            ("nznrnfrfntjfmvfwmzdfjlvtqnbhcprsg", 10, 29),
+            ("zcfzfwzzqfrljwzlrfnpqdbhtmscgvjw", 11, 26),
+        ];
+
+        for (input, packet, message) in examples {
+            assert_eq!(get_start_of_packet_marker(input), Some(packet));
+            assert_eq!(get_start_of_message_marker(input), Some(message));
+        }
+    }
+
+    #[test]
+    fn test_unique_window_large_size() {
+        // 200 distinct bytes after a run of repeats
+        let input: Vec<u8> = [7; 50].into_iter().chain(0..=199).collect();
+
+        assert_eq!(get_end_unique_window_in(&input[..], 200).unwrap(), Some(250));
+        assert_eq!(get_end_unique_window_in(&input[..], 201).unwrap(), None);
+        assert_eq!(get_end_unique_window_in(&input[..], 1).unwrap(), Some(1));
+    }
+
+    /// A stream failing on every read, chained after some bytes in the tests
+    struct BrokenStream;
+
+    impl Read for BrokenStream {
+        fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+            Err(io::Error::other("stream broke"))
+        }
+    }
+
+    #[test]
+    fn test_read_error() {
+        let error = get_end_unique_window_in(b"abab".chain(BrokenStream), 4).unwrap_err();
+        assert_eq!(error.to_string(), "stream broke");
+
+        // The marker is found before the stream breaks
+        assert_eq!(get_end_unique_window_in(b"abcd".chain(BrokenStream), 4).unwrap(), Some(4));
+
+        assert!(scan_markers(b"abcd".chain(BrokenStream), &[4], Occurrence::All).is_err());
+        assert_eq!(scan_markers(b"abcd".chain(BrokenStream), &[4], Occurrence::First).unwrap(), vec![vec![4]]);
+    }
+
+    #[test]
+    fn test_scan_markers() {
+        let input = "abcabcdd";
+
+        assert_eq!(scan_markers(input.as_bytes(), &[3, 4], Occurrence::First).unwrap(), vec![vec![3], vec![7]]);
+        assert_eq!(scan_markers(input.as_bytes(), &[3, 4], Occurrence::All).unwrap(), vec![vec![3, 4, 5, 6, 7], vec![7]]);
+        assert_eq!(scan_markers(input.as_bytes(), &[3, 4], Occurrence::Nth(2)).unwrap(), vec![vec![4], vec![]]);
+        assert_eq!(scan_markers(input.as_bytes(), &[5], Occurrence::All).unwrap(), vec![Vec::<usize>::new()]);
+
+        let args = |args: &[&str]| args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>();
+        assert_eq!(parse_scan_args(&args(&["all", "4", "14"])), Some((Occurrence::All, vec![4, 14])));
+        assert_eq!(parse_scan_args(&args(&["2", "4"])), Some((Occurrence::Nth(2), vec![4])));
+        assert_eq!(parse_scan_args(&args(&["first"])), None);
+        assert_eq!(parse_scan_args(&args(&["0", "4"])), None);
+        assert_eq!(parse_scan_args(&args(&["some", "4"])), None);
+        assert_eq!(parse_scan_args(&args(&["all", "x"])), None);
+        assert_eq!(parse_scan_args(&[]), None);
+
+        assert_eq!(parse_positive("14"), Some(14));
+        assert_eq!(parse_positive("0"), None);
+        assert_eq!(parse_positive("x"), None);
+    }
+
+    #[test]
+    fn test_no_marker() {
+        // Shorter than the window
+        assert_eq!(get_start_of_packet_marker(""), None);
+        assert_eq!(get_start_of_packet_marker("abc"), None);
+        assert_eq!(get_start_of_message_marker("abcdefghijklm"), None);
+
+        // A marker right at the end is still found
+        assert_eq!(get_start_of_packet_marker("abcd"), Some(4));
+        assert_eq!(get_start_of_packet_marker("aabcd"), Some(5));
+        assert_eq!(get_start_of_packet_marker("aabcc"), None);
+
+        // The newline ending the input file cannot complete a marker
+        assert_eq!(get_start_of_packet_marker("aaabc\n"), None);
+        assert_eq!(get_start_of_message_marker("abcdefghijklm\n"), None);
+        assert_eq!(get_start_of_packet_marker("aabcd\n"), Some(5));
+    }
+
+    #[test]
+    fn test_frames() {
+        let input = b"aaabcdxxyyxxbcdeqqqq";
+
+        // The second marker is xbcd, which starts inside the run of x
+        let frames: Vec<_> = Frames::new(input, 4).collect();
+        assert_eq!(frames, vec![
+            Frame { marker_offset: 2, offset: 6, payload: b"xxyyx" },
+            Frame { marker_offset: 11, offset: 15, payload: b"eqqqq" },
+        ]);
+
+        // A marker directly followed by another gives an empty payload
+        let frames: Vec<_> = Frames::new(b"abcdefgh", 4).map(|frame| frame.payload).collect();
+        assert_eq!(frames, vec![&b""[..], &b""[..]]);
+
+        assert_eq!(Frames::new(b"aaaa", 4).next(), None);
     }
 }
@@ -1,217 +1,435 @@
-use std::{rc::{Rc, Weak}, cell::RefCell, iter::Peekable, str::Lines, vec, fs, borrow::Cow};
+use std::{iter::{Peekable, Enumerate}, str::Lines, fs, borrow::Cow, fmt::Display};
 
 use ptree::{TreeItem, print_tree};
 
-#[derive(Clone)]
-struct StorageItem {
-    parent: Weak<RefCell<StorageItem>>,
+/// Index of a node in the `FileSystem` arena
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct NodeId(usize);
+
+#[derive(Debug)]
+struct Dir {
+    parent: Option<NodeId>,
     name: String,
+    /// Total size of all files below this directory
     size: usize,
-    children: Option<RefCell<Vec<Rc<RefCell<StorageItem>>>>>
+    children: Vec<NodeId>
+}
+
+#[derive(Debug)]
+struct File {
+    parent: NodeId,
+    name: String,
+    size: usize
 }
 
-impl StorageItem {
-    fn add_directory(base: &Rc<RefCell<StorageItem>>, dir_name: &str) {
-        if base.borrow().children.is_none() {
-            panic!("Trying to add directory to file.");
+#[derive(Debug)]
+enum Node {
+    Dir(Dir),
+    File(File)
+}
+
+impl Node {
+    fn name(&self) -> &str {
+        match self {
+            Node::Dir(dir) => &dir.name,
+            Node::File(file) => &file.name,
         }
+    }
 
-        if let Some(ref children) = base.borrow().children {
-            let new_directory = StorageItem {
-                parent: Rc::downgrade(&base),
-                name: dir_name.to_string(),
-                size: 0,
-                children: Some(RefCell::new(vec![]))
-            };
+    fn size(&self) -> usize {
+        match self {
+            Node::Dir(dir) => dir.size,
+            Node::File(file) => file.size,
+        }
+    }
 
-            children.borrow_mut().push(Rc::new(RefCell::new(new_directory)));
+    fn parent(&self) -> Option<NodeId> {
+        match self {
+            Node::Dir(dir) => dir.parent,
+            Node::File(file) => Some(file.parent),
         }
     }
+}
 
-    fn add_file(base: &Rc<RefCell<StorageItem>>, file_name: &str, file_size: &str) {
-        if base.borrow().children.is_none() {
-            panic!("Trying to add file to file.");
+/// All storage items in one arena, linked by index, the root directory is always the first node
+struct FileSystem {
+    nodes: Vec<Node>
+}
+
+impl FileSystem {
+    fn new() -> FileSystem {
+        FileSystem {
+            nodes: vec![Node::Dir(Dir { parent: None, name: "/".to_string(), size: 0, children: vec![] })]
         }
-        let mut base_mut_borrowed = base.borrow_mut();
-        if let Some(ref children) = base_mut_borrowed.children {
-            let file_size = file_size.parse().unwrap();
-
-            let new_file = StorageItem {
-                parent: Rc::downgrade(&base),
-                name: file_name.to_string(),
-                size: file_size,
-                children: None
-            };
+    }
 
-            children.borrow_mut().push(Rc::new(RefCell::new(new_file)));
+    fn root(&self) -> NodeId {
+        NodeId(0)
+    }
 
-            // Recursively add the size upwards the tree
-            base_mut_borrowed.add_size(file_size);
+    fn node(&self, id: NodeId) -> &Node {
+        &self.nodes[id.0]
+    }
+
+    fn dir_mut(&mut self, id: NodeId) -> &mut Dir {
+        match &mut self.nodes[id.0] {
+            Node::Dir(dir) => dir,
+            Node::File(file) => panic!("Trying to add to file {:?}.", file.name),
         }
     }
 
-    fn add_size(&mut self, file_size: usize) {
-        // Add size to itself
-        self.size += file_size;
+    fn children(&self, id: NodeId) -> &[NodeId] {
+        match self.node(id) {
+            Node::Dir(dir) => &dir.children,
+            Node::File(_) => &[],
+        }
+    }
+
+    /// Finds the file or directory called `name` directly below `id`
+    fn child(&self, id: NodeId, name: &str) -> Option<NodeId> {
+        self.children(id).iter().copied().find(|child| self.node(*child).name() == name)
+    }
+
+    /// Finds the child directory called `name`
+    fn child_dir(&self, id: NodeId, name: &str) -> Option<NodeId> {
+        self.child(id, name).filter(|child| matches!(self.node(*child), Node::Dir(_)))
+    }
 
-        // Add size to parent if it exists
-        if let Some(parent) = self.parent.upgrade() {
-            parent.borrow_mut().add_size(file_size);
+    /// Follows a path like `a/b`, `../c` or `/a/b` from `base`, absolute paths start at the root
+    fn resolve(&self, base: NodeId, path: &str) -> Result<NodeId, PathError> {
+        let mut loc = if path.starts_with('/') { self.root() } else { base };
+
+        for segment in path.split('/').filter(|segment| !segment.is_empty() && *segment != ".") {
+            loc = if segment == ".." {
+                self.node(loc).parent().ok_or(PathError::AboveRoot)?
+            } else {
+                self.child_dir(loc, segment).ok_or_else(|| PathError::UnknownDirectory(segment.to_string()))?
+            };
         }
+
+        Ok(loc)
+    }
+
+    fn add_directory(&mut self, base: NodeId, dir_name: &str) -> NodeId {
+        let id = NodeId(self.nodes.len());
+
+        self.dir_mut(base).children.push(id);
+        self.nodes.push(Node::Dir(Dir { parent: Some(base), name: dir_name.to_string(), size: 0, children: vec![] }));
+
+        id
+    }
+
+    fn add_file(&mut self, base: NodeId, file_name: &str, file_size: usize) -> NodeId {
+        let id = NodeId(self.nodes.len());
+
+        self.dir_mut(base).children.push(id);
+        self.nodes.push(Node::File(File { parent: base, name: file_name.to_string(), size: file_size }));
+
+        // Add the size to every directory up the tree
+        let mut ancestor = Some(base);
+        while let Some(dir) = ancestor {
+            let dir = self.dir_mut(dir);
+            dir.size += file_size;
+            ancestor = dir.parent;
+        }
+
+        id
+    }
+
+    fn directories(&self) -> impl Iterator<Item = &Dir> {
+        self.nodes.iter().filter_map(|node| match node {
+            Node::Dir(dir) => Some(dir),
+            Node::File(_) => None,
+        })
+    }
+
+    /// View of a node for printing, borrowing the arena instead of copying subtrees
+    fn tree(&self, id: NodeId) -> TreeNode<'_> {
+        TreeNode { fs: self, id }
     }
 }
 
-impl TreeItem for StorageItem {
+#[derive(Clone)]
+struct TreeNode<'a> {
+    fs: &'a FileSystem,
+    id: NodeId
+}
+
+impl TreeItem for TreeNode<'_> {
     type Child = Self;
 
     fn write_self<W: std::io::Write>(&self, f: &mut W, _: &ptree::Style) -> std::io::Result<()> {
-        if self.children.is_some() {
-            // Directory
-            write!(f, "dir {:?} ({:?})", self.name, self.size)
-        } else {
-            // File
-            write!(f, "file {:?} ({:?})", self.name, self.size)
+        match self.fs.node(self.id) {
+            Node::Dir(dir) => write!(f, "dir {:?} ({:?})", dir.name, dir.size),
+            Node::File(file) => write!(f, "file {:?} ({:?})", file.name, file.size),
         }
     }
 
-    fn children(&self) -> std::borrow::Cow<[Self::Child]> {
-        if let Some(ref children) = self.children {
-            // Directory
-            let array: Vec<StorageItem> = children.borrow().iter().map(|c| c.borrow().clone()).collect();
-            Cow::from(array)
-        } else {
-            // File
-            Cow::from(vec![])
+    fn children(&self) -> Cow<'_, [Self::Child]> {
+        Cow::from(self.fs.children(self.id).iter().map(|child| self.fs.tree(*child)).collect::<Vec<_>>())
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum PathError {
+    /// No directory with this name was listed in the directory the path passes through
+    UnknownDirectory(String),
+    /// The path goes up from the root
+    AboveRoot
+}
+
+/// A `cd` in the transcript that could not be followed, lines are numbered from 1
+#[derive(Debug, PartialEq)]
+struct CdError {
+    line: usize,
+    path: String,
+    error: PathError
+}
+
+impl Display for CdError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.error {
+            PathError::UnknownDirectory(name) => write!(f, "line {}: cd {}: no directory {:?}", self.line, self.path, name),
+            PathError::AboveRoot => write!(f, "line {}: cd {}: goes above the root", self.line, self.path),
         }
-    } 
+    }
 }
 
 struct StorageReader<'a> {
-    loc: Rc<RefCell<StorageItem>>,
-    iter: Peekable<Lines<'a>>
+    fs: FileSystem,
+    loc: NodeId,
+    iter: Peekable<Enumerate<Lines<'a>>>
 }
 
 impl<'a> StorageReader<'a> {
-    fn build_and_read(console: String) -> Rc<RefCell<StorageItem>> {
-        let root = Rc::new(RefCell::new(StorageItem {
-            parent: Weak::new(),
-            name: "/".to_string(),
-            size: 0,
-            children: Some(RefCell::new(vec![]))
-        }));
+    fn build_and_read(console: &'a str) -> Result<FileSystem, CdError> {
+        let fs = FileSystem::new();
 
         let mut reader = StorageReader {
-            loc: Rc::clone(&root),
-            iter: console.lines().peekable()
+            loc: fs.root(),
+            fs,
+            iter: console.lines().enumerate().peekable()
         };
 
-        reader.read();
+        reader.read()?;
 
-        root
+        Ok(reader.fs)
     }
 
-    fn read(&mut self)  {
-        while let Some(line) = self.iter.next() {
+    fn read(&mut self) -> Result<(), CdError> {
+        while let Some((index, line)) = self.iter.next() {
             let parts: Vec<_> = line.split_whitespace().collect();
 
             // Assuming at this point we are always running a command starting with $
             match parts[1] {
                 "ls" => self.handle_ls(),
-                "cd" => self.handle_cd(parts[2]),
+                "cd" => self.handle_cd(parts[2]).map_err(|error| CdError { line: index + 1, path: parts[2].to_string(), error })?,
                 _ => unreachable!()
             }
         }
+
+        Ok(())
     }
 
-    /// Handles the lines after a ls command
-    /// Once it reaches another command, it breaks 
-    fn handle_ls<'b : 'a>(&mut self) {
-        while let Some(line) = self.iter.peek() {
+    /// Handles the lines after a ls command, skipping entries already listed in this directory
+    /// Once it reaches another command, it breaks
+    fn handle_ls(&mut self) {
+        while let Some((_, line)) = self.iter.peek() {
 
             let parts: Vec<_> = line.split_whitespace().collect();
 
             // Break out of it upon next command
-            if parts[0] == "$" { 
-                break; 
+            if parts[0] == "$" {
+                break;
             }
 
             self.iter.next();
 
+            // Listing a directory again must not count its files twice
+            if self.fs.child(self.loc, parts[1]).is_some() {
+                continue;
+            }
+
             match parts[0] {
-                "dir" => StorageItem::add_directory(&self.loc, parts[1]),
-                filesize => StorageItem::add_file(&self.loc, parts[1], filesize)
+                "dir" => self.fs.add_directory(self.loc, parts[1]),
+                filesize => self.fs.add_file(self.loc, parts[1], filesize.parse().unwrap())
             };
         }
     }
 
-    fn handle_cd(&mut self, loc: &str) {
-        if loc == ".." {
-            let smth = self.loc.borrow().parent.upgrade().unwrap();
-            self.loc = smth;
-        } else {
-            let current_loc = self.loc.to_owned();
-            let current_loc = current_loc.borrow();
-
-            if let Some(ref children) = current_loc.children {
-                let children = children.borrow();
-
-                for child in children.iter() {
-                    if child.borrow().children.is_some() && child.borrow().name == loc {
-                        let new_loc = Rc::clone(child);
-                        self.loc = new_loc;
-                        break;
-                    }
-                }
-            } 
-        }
+    /// Moves to the directory at `path`, an error ends the whole read
+    fn handle_cd(&mut self, path: &str) -> Result<(), PathError> {
+        self.loc = self.fs.resolve(self.loc, path)?;
+
+        Ok(())
     }
 }
 
+const DISK_SIZE: usize = 70000000;
+const UPDATE_SIZE: usize = 30000000;
 
-fn check_sum_sub_100_directories(item: &StorageItem, sum: &mut usize) {
-    if item.children.is_some() {
-        // Directory
-        if item.size <= 100000 {
-            *sum += item.size;
-        }
-
-        for child in item.children.as_ref().unwrap().borrow().iter() {
-            check_sum_sub_100_directories(&child.borrow(), sum);
-        }
-    } 
+fn check_sum_sub_100_directories(fs: &FileSystem) -> usize {
+    fs.directories().map(|dir| dir.size).filter(|size| *size <= 100000).sum()
 }
 
-fn check_smallest_super_100(item: &StorageItem, min: &mut usize) {
-    if item.children.is_some() {
-        // Directory
-        if item.size >= 3562874 && item.size < *min {
-            *min = item.size;
-        }
+/// Size of the smallest directory that frees up enough space for the update when deleted,
+/// the root always qualifies as it holds everything in use
+fn check_smallest_super_100(fs: &FileSystem) -> usize {
+    let used = fs.node(fs.root()).size();
+    let needed = used.saturating_sub(DISK_SIZE - UPDATE_SIZE);
 
-        for child in item.children.as_ref().unwrap().borrow().iter() {
-            check_smallest_super_100(&child.borrow(), min);
-        }
-    } 
+    fs.directories().map(|dir| dir.size).filter(|size| *size >= needed).min().unwrap()
 }
 
 fn main() {
     let input = fs::read_to_string("input.txt").unwrap();
 
-    let root = StorageReader::build_and_read(input);
+    let root = match StorageReader::build_and_read(&input) {
+        Ok(root) => root,
+        Err(error) => {
+            println!("Cannot reconstruct filesystem: {error}");
+            return;
+        }
+    };
 
-    let root = root.borrow().clone();
+    print_tree(&root.tree(root.root())).unwrap();
 
-    print_tree(&root).unwrap();
+    let sum = check_sum_sub_100_directories(&root);
 
-    let mut sum = 0;
+    println!("{sum}");
 
-    check_sum_sub_100_directories(&root, &mut sum);
+    let min = check_smallest_super_100(&root);
 
-    println!("{sum}");
+    println!("{min}");
+}
 
-    let mut min = usize::MAX;
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = "$ cd /
+$ ls
+dir a
+14848514 b.txt
+8504156 c.dat
+dir d
+$ cd a
+$ ls
+dir e
+29116 f
+2557 g
+62596 h.lst
+$ cd e
+$ ls
+584 i
+$ cd ..
+$ cd ..
+$ cd d
+$ ls
+4060174 j
+8033020 d.log
+5626152 d.ext
+7214296 k";
+
+    #[test]
+    fn test_build_and_read() {
+        let fs = StorageReader::build_and_read(EXAMPLE).unwrap();
+
+        let a = fs.child_dir(fs.root(), "a").unwrap();
+        let e = fs.child_dir(a, "e").unwrap();
+
+        assert_eq!(fs.node(fs.root()).size(), 48381165);
+        assert_eq!(fs.node(a).size(), 94853);
+        assert_eq!(fs.node(e).size(), 584);
+        assert_eq!(fs.node(e).parent(), Some(a));
+        assert_eq!(fs.node(fs.children(e)[0]).name(), "i");
+        assert_eq!(fs.child_dir(fs.root(), "b.txt"), None);
+    }
 
-    check_smallest_super_100(&root, &mut min);
+    #[test]
+    fn test_both_parts() {
+        let fs = StorageReader::build_and_read(EXAMPLE).unwrap();
 
-    println!("{min}");
+        assert_eq!(check_sum_sub_100_directories(&fs), 95437);
+        assert_eq!(check_smallest_super_100(&fs), 24933642);
+
+        // More in use than fits on the disk
+        let fs = StorageReader::build_and_read("$ cd /\n$ ls\ndir a\n80000000 b\n$ cd a\n$ ls\n10 c").unwrap();
+        assert_eq!(check_smallest_super_100(&fs), 80000010);
+    }
+
+    #[test]
+    fn test_cd_paths() {
+        let transcript = "$ cd /
+$ ls
+dir a
+$ cd a
+$ ls
+dir b
+$ cd b
+$ ls
+dir c
+$ cd /a/b/c
+$ ls
+10 x
+$ cd ../../..
+$ cd a/./b/c/..
+$ ls
+20 y
+$ cd /
+$ ls
+30 z";
+        let fs = StorageReader::build_and_read(transcript).unwrap();
+
+        let b = fs.resolve(fs.root(), "a/b").unwrap();
+        assert_eq!(fs.node(b).size(), 30);
+        assert_eq!(fs.node(fs.resolve(b, "c").unwrap()).size(), 10);
+        assert_eq!(fs.node(fs.root()).size(), 60);
+        assert_eq!(fs.resolve(b, "/"), Ok(fs.root()));
+        assert_eq!(fs.resolve(b, "../.."), Ok(fs.root()));
+    }
+
+    #[test]
+    fn test_repeated_ls() {
+        let transcript = "$ cd /
+$ ls
+dir a
+10 b
+$ ls
+dir a
+10 b
+$ cd a
+$ ls
+20 c
+$ cd /
+$ ls
+dir a
+10 b
+$ cd a
+$ ls
+20 c";
+        let fs = StorageReader::build_and_read(transcript).unwrap();
+
+        let a = fs.child_dir(fs.root(), "a").unwrap();
+        assert_eq!(fs.children(fs.root()).len(), 2);
+        assert_eq!(fs.children(a).len(), 1);
+        assert_eq!(fs.node(a).size(), 20);
+        assert_eq!(fs.node(fs.root()).size(), 30);
+    }
+
+    #[test]
+    fn test_cd_errors() {
+        assert_eq!(
+            StorageReader::build_and_read("$ cd /\n$ ls\ndir a\n$ cd b").err(),
+            Some(CdError { line: 4, path: "b".to_string(), error: PathError::UnknownDirectory("b".to_string()) })
+        );
+        assert_eq!(
+            StorageReader::build_and_read("$ cd /\n$ cd ..").err(),
+            Some(CdError { line: 2, path: "..".to_string(), error: PathError::AboveRoot })
+        );
+
+        let fs = StorageReader::build_and_read(EXAMPLE).unwrap();
+        assert_eq!(fs.resolve(fs.root(), "a/x/e"), Err(PathError::UnknownDirectory("x".to_string())));
+        assert_eq!(fs.resolve(fs.root(), "b.txt"), Err(PathError::UnknownDirectory("b.txt".to_string())));
+    }
 }